@@ -0,0 +1,1020 @@
+use crate::config::{Config, ConfigOverrides};
+use crate::discover::Discovery;
+use crate::lexer::Lexer;
+use crate::report::{FileReport, Report, ReportFormat};
+use crate::rewrite::Rewriter;
+use crate::writer::{GeneratedModule, Writer};
+use anyhow::{anyhow, Context, Result};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use rayon::prelude::*;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing::{debug, debug_span, Level};
+
+// Path argument that selects the stdin/stdout filter mode.
+const STDIN_PATH: &str = "-";
+
+/// Upgrades FRAME v1 `benchmarks!` invocations to v2 `#[benchmarks]` modules.
+#[derive(Debug, Parser)]
+#[command(name = "benchmark-upgrader", version, about)]
+pub struct Cli {
+    /// Print diagnostics to stderr: `-v` for each pipeline stage, `-vv` for every line.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Convert a v1 `benchmarks!` file and print the generated v2 module.
+    Convert(ConvertArgs),
+    /// Undo an `--in-place` conversion by restoring `<file>.v1.bak`.
+    Restore {
+        /// Path of the converted file, or a directory to restore every backup in it.
+        path: PathBuf,
+    },
+    /// List the files under a directory that still use v1 benchmark macros.
+    Scan {
+        /// Directory to search. `.gitignore` rules are respected.
+        dir: PathBuf,
+        /// Also look for invocations of this macro, may be repeated
+        #[arg(long = "extra-macro", value_name = "NAME")]
+        extra_macros: Vec<String>,
+        /// Also look for invocations of this instance macro, may be repeated
+        #[arg(long = "extra-instance-macro", value_name = "NAME")]
+        extra_instance_macros: Vec<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+#[command(after_help = "Exit codes with --check:
+  0  no v1 `benchmarks!` invocation remains
+  1  v1 benchmarks remain and all of them convert cleanly
+  2  at least one file or benchmark fails to convert
+
+Without --check the exit code is 1 if any benchmark fails to convert. Files with such
+benchmarks are not written by --in-place or --output, so no benchmark is lost.
+
+Reading from stdin (PATH `-`) is meant for piping an editor selection through the tool.
+Only the generated v2 module is written, as for a file PATH, so text around the
+`benchmarks!` invocation is dropped. If the input or any of its benchmarks fails to
+convert, the input is written back to stdout unchanged and the error goes to stderr, so
+the selection is never lost; `--output` is then left unwritten.")]
+pub struct ConvertArgs {
+    /// Path to the file containing the `benchmarks!` invocation, or a directory to
+    /// convert every file found by `scan`. With `-` the input is read from stdin and the
+    /// generated module is written to stdout.
+    pub path: PathBuf,
+    /// Write the generated module to this file instead of stdout.
+    #[arg(short, long, value_name = "FILE", conflicts_with = "in_place")]
+    pub output: Option<PathBuf>,
+    /// Replace the `benchmarks!` invocation in the file itself, keeping the
+    /// original as `<file>.v1.bak`.
+    #[arg(short, long)]
+    pub in_place: bool,
+    /// Write nothing, print one line per file and exit with a status describing
+    /// whether v1 code remains and whether it converts.
+    #[arg(long, conflicts_with_all = ["output", "in_place"])]
+    pub check: bool,
+    /// Print a unified diff of the proposed change instead of the module. The diff
+    /// applies with `git apply` or `patch -p1` from the current directory, so PATH has
+    /// to be inside it.
+    #[arg(long, conflicts_with_all = ["output", "in_place", "check"])]
+    pub diff: bool,
+    /// Print a report of every converted file instead of the module. Files are still
+    /// written with `--in-place` or `--output`.
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["check", "diff"])]
+    pub report: Option<ReportFormat>,
+    /// Number of files converted in parallel when PATH is a directory. Defaults to the
+    /// number of CPUs; output is printed in path order regardless.
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+    #[command(flatten)]
+    pub config: ConfigOverrides,
+}
+
+/// Outcome of `convert --check` for one file, ordered from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    /// No v1 invocation is left.
+    Upgraded = 0,
+    /// A v1 invocation is left and converts without errors.
+    Convertible = 1,
+    /// The file cannot be read or some of its benchmarks fail to convert.
+    Failed = 2,
+}
+
+// A converted file along with what it prints, buffered so that files converted in
+// parallel are printed in order.
+struct ConvertedFile {
+    generated: GeneratedModule,
+    stdout: String,
+    stderr: String,
+}
+
+impl ConvertedFile {
+    fn emit(&self) {
+        eprint!("{}", self.stderr);
+        print!("{}", self.stdout);
+    }
+}
+
+impl Cli {
+    /// Installs the stderr logger for the requested verbosity. Nothing is logged by default.
+    pub fn init_logging(&self) {
+        if let Some(level) = Self::log_level(self.verbose) {
+            tracing_subscriber::fmt()
+                .with_max_level(level)
+                .with_writer(io::stderr)
+                .with_ansi(io::stderr().is_terminal())
+                .without_time()
+                .init();
+        }
+    }
+
+    fn log_level(verbose: u8) -> Option<Level> {
+        match verbose {
+            0 => None,
+            1 => Some(Level::DEBUG),
+            _ => Some(Level::TRACE),
+        }
+    }
+
+    pub fn run(self) -> Result<ExitCode> {
+        match self.command {
+            Command::Convert(args) => {
+                if args.check {
+                    let status = Self::check(&args.path, &args.config, args.jobs);
+                    return Ok(ExitCode::from(status as u8));
+                }
+                if args.report.is_some() {
+                    return Self::report(&args);
+                }
+                let complete = if args.path == Path::new(STDIN_PATH) {
+                    Self::filter(&args)?.skipped.is_empty()
+                } else if args.path.is_dir() {
+                    Self::emit_dir(&args, Self::convert_dir(&args)?)?;
+                    true
+                } else {
+                    let converted = Self::convert(&args, &args.path)?;
+                    converted.emit();
+                    converted.generated.skipped.is_empty()
+                };
+                Ok(if complete {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                })
+            }
+            Command::Restore { path } => {
+                Self::restore(&path)?;
+                Ok(ExitCode::SUCCESS)
+            }
+            Command::Scan {
+                dir,
+                extra_macros,
+                extra_instance_macros,
+            } => {
+                let overrides = ConfigOverrides {
+                    extra_macros,
+                    extra_instance_macros,
+                    ..Default::default()
+                };
+                Self::scan(&dir, &overrides)?;
+                Ok(ExitCode::SUCCESS)
+            }
+        }
+    }
+
+    // Restores `path`, or every backup below it when it is a directory. A file that fails
+    // to restore is reported and the others are restored regardless.
+    fn restore(path: &Path) -> Result<()> {
+        if !path.is_dir() {
+            let backup = Rewriter::restore(path)?;
+            eprintln!("restored {} from {}", path.display(), backup.display());
+            return Ok(());
+        }
+        let files: Vec<PathBuf> = Discovery::backups(path)?
+            .iter()
+            .filter_map(|backup| Rewriter::original_path(backup))
+            .collect();
+        let mut failed = 0;
+        for path in &files {
+            match Rewriter::restore(path) {
+                Ok(backup) => eprintln!("restored {} from {}", path.display(), backup.display()),
+                Err(e) => {
+                    eprintln!("error: {:#}", e);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(anyhow!(
+                "{} of {} files failed to restore",
+                failed,
+                files.len()
+            ));
+        }
+        Ok(())
+    }
+
+    fn scan(dir: &Path, overrides: &ConfigOverrides) -> Result<()> {
+        let files = Discovery::scan(dir, overrides)?;
+        let mut total = 0;
+        for file in &files {
+            match file.benchmarks {
+                Some(count) => {
+                    total += count;
+                    println!("{:>5}  {}", count, file.path.display());
+                }
+                None => println!("{:>5}  {}", "?", file.path.display()),
+            }
+            if let Some(error) = &file.error {
+                eprintln!("warning: {}", error);
+            }
+        }
+        println!("{:>5}  total in {} files", total, files.len());
+        Ok(())
+    }
+
+    // Prints one line per file and returns the worst status among them.
+    fn check(path: &Path, overrides: &ConfigOverrides, jobs: Option<NonZeroUsize>) -> CheckStatus {
+        let files = if path.is_dir() {
+            match Discovery::scan(path, overrides) {
+                Ok(files) => files.into_iter().map(|file| file.path).collect(),
+                Err(e) => {
+                    println!("{}: error: {:#}", path.display(), e);
+                    return CheckStatus::Failed;
+                }
+            }
+        } else {
+            vec![path.to_path_buf()]
+        };
+        if files.is_empty() {
+            println!("{}: ok: no v1 benchmarks found", path.display());
+            return CheckStatus::Upgraded;
+        }
+
+        let results = match Self::par_map(jobs, &files, |file| Self::check_file(file, overrides)) {
+            Ok(results) => results,
+            Err(e) => {
+                println!("{}: error: {:#}", path.display(), e);
+                return CheckStatus::Failed;
+            }
+        };
+        let mut worst = CheckStatus::Upgraded;
+        for (file, (status, reason)) in files.iter().zip(results) {
+            let label = match status {
+                CheckStatus::Upgraded => "ok",
+                CheckStatus::Convertible => "v1",
+                CheckStatus::Failed => "error",
+            };
+            println!("{}: {}: {}", file.display(), label, reason);
+            worst = worst.max(status);
+        }
+        worst
+    }
+
+    fn check_file(path: &Path, overrides: &ConfigOverrides) -> (CheckStatus, String) {
+        let input = match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(e) => return (CheckStatus::Failed, format!("failed to read file: {}", e)),
+        };
+        let config = match Config::load(path, overrides) {
+            Ok(config) => config,
+            Err(e) => return (CheckStatus::Failed, format!("{:#}", e)),
+        };
+        match Lexer::invocation_span(&input, &config) {
+            Ok(None) => {
+                return (
+                    CheckStatus::Upgraded,
+                    "no v1 benchmarks! invocation".to_string(),
+                )
+            }
+            Err(e) => return (CheckStatus::Failed, e),
+            Ok(Some(_)) => {}
+        }
+        match Self::generate(path, &input, &config) {
+            Ok((_, generated)) if generated.skipped.is_empty() => (
+                CheckStatus::Convertible,
+                "v1 benchmarks! invocation converts cleanly".to_string(),
+            ),
+            Ok((_, generated)) => {
+                let names: Vec<&str> = generated
+                    .skipped
+                    .iter()
+                    .map(|skipped| skipped.name.as_str())
+                    .collect();
+                (
+                    CheckStatus::Failed,
+                    format!(
+                        "{} benchmark(s) fail to convert: {}",
+                        names.len(),
+                        names.join(", ")
+                    ),
+                )
+            }
+            Err(e) => (CheckStatus::Failed, format!("{:#}", e)),
+        }
+    }
+
+    // Converts every file found by `scan` below PATH and returns the result of each, in
+    // path order.
+    fn convert_dir(args: &ConvertArgs) -> Result<Vec<(PathBuf, Result<ConvertedFile>)>> {
+        if args.output.is_some() {
+            return Err(anyhow!(
+                "--output cannot be used when converting a directory"
+            ));
+        }
+        let paths: Vec<PathBuf> = Discovery::scan(&args.path, &args.config)?
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        let results = Self::par_map(args.jobs, &paths, |path| Self::convert(args, path))?;
+        Ok(paths.into_iter().zip(results).collect())
+    }
+
+    // Prints the converted files of a directory and fails if any of them did not convert.
+    fn emit_dir(args: &ConvertArgs, files: Vec<(PathBuf, Result<ConvertedFile>)>) -> Result<()> {
+        let total = files.len();
+        let mut failed = 0;
+        for (path, result) in files {
+            match result {
+                Ok(converted) => {
+                    if !args.in_place && !args.diff {
+                        println!("// {}", path.display());
+                    }
+                    converted.emit();
+                    if !converted.generated.skipped.is_empty() {
+                        failed += 1;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {:#}", e);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(anyhow!("{} of {} files failed to convert", failed, total));
+        }
+        Ok(())
+    }
+
+    // Runs `f` over `items` on up to `jobs` threads and returns the results in the order
+    // of `items`.
+    fn par_map<T, R>(
+        jobs: Option<NonZeroUsize>,
+        items: &[T],
+        f: impl Fn(&T) -> R + Send + Sync,
+    ) -> Result<Vec<R>>
+    where
+        T: Sync,
+        R: Send,
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs.map_or(0, NonZeroUsize::get))
+            .build()
+            .context("Failed to start worker threads")?;
+        Ok(pool.install(|| items.par_iter().map(f).collect()))
+    }
+
+    // Converts like `run` but prints a report of the results instead of the code.
+    fn report(args: &ConvertArgs) -> Result<ExitCode> {
+        let files = if args.path == Path::new(STDIN_PATH) {
+            vec![FileReport::new(Path::new("<stdin>"), &Self::filter(args))]
+        } else {
+            let results = if args.path.is_dir() {
+                Self::convert_dir(args)?
+            } else {
+                vec![(args.path.clone(), Self::convert(args, &args.path))]
+            };
+            results
+                .into_iter()
+                .map(|(path, result)| {
+                    let result = result.map(|converted| {
+                        eprint!("{}", converted.stderr);
+                        converted.generated
+                    });
+                    FileReport::new(&Self::display_path(&path), &result)
+                })
+                .collect()
+        };
+        let failed = files.iter().any(|file| !file.success);
+        let report = Report::new(files);
+        match args.report {
+            Some(ReportFormat::Json) | None => println!("{}", report.to_json()?),
+        }
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+
+    // Reads a snippet from stdin and writes only the generated module, so editors can
+    // pipe a selection through the binary. A snippet that does not convert completely is
+    // written back unchanged instead, so the selection is never lost.
+    fn filter(args: &ConvertArgs) -> Result<GeneratedModule> {
+        if args.in_place || args.check || args.diff {
+            return Err(anyhow!(
+                "--in-place, --check and --diff need a file path, not stdin"
+            ));
+        }
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read stdin")?;
+        let (output, result) = Self::filter_source(args, &input);
+        match &args.output {
+            Some(path) => match &result {
+                Ok(generated) if generated.skipped.is_empty() => {
+                    fs::write(path, &generated.code)
+                        .with_context(|| format!("Failed to write {}", path.display()))?
+                }
+                _ => {}
+            },
+            None if args.report.is_none() => print!("{}", output),
+            None => {}
+        }
+        result
+    }
+
+    // Converts a snippet read from stdin and returns what replaces it on stdout: the
+    // generated module, or the snippet itself if it fails to convert.
+    fn filter_source(args: &ConvertArgs, input: &str) -> (String, Result<GeneratedModule>) {
+        let path = Path::new("<stdin>");
+        // Without a file the configuration is looked up from the current directory
+        let result = Config::load(Path::new("."), &args.config)
+            .and_then(|config| Self::generate(path, input, &config))
+            .map(|(_, generated)| generated);
+        match &result {
+            Ok(generated) if generated.skipped.is_empty() => (generated.code.clone(), result),
+            Ok(generated) => {
+                eprint!("{}", Self::skipped_warnings(path, generated));
+                // Replacing the selection would delete the benchmarks that failed to convert
+                eprintln!("error: <stdin> left unchanged, some benchmarks fail to convert");
+                (input.to_string(), result)
+            }
+            Err(_) => (input.to_string(), result),
+        }
+    }
+
+    fn skipped_warnings(path: &Path, generated: &GeneratedModule) -> String {
+        generated
+            .skipped
+            .iter()
+            .map(|skipped| {
+                format!(
+                    "warning: {}: skipped benchmark `{}`: {}\n",
+                    path.display(),
+                    skipped.name,
+                    skipped.reason
+                )
+            })
+            .collect()
+    }
+
+    // Converts one file. Nothing is printed here so that files converted in parallel
+    // can be emitted in order afterwards.
+    fn convert(args: &ConvertArgs, path: &Path) -> Result<ConvertedFile> {
+        let input = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config = Config::load(path, &args.config)?;
+        let (span, generated) = Self::generate(path, &input, &config)?;
+        let mut converted = ConvertedFile {
+            stdout: String::new(),
+            stderr: Self::skipped_warnings(path, &generated),
+            generated,
+        };
+        let code = &converted.generated.code;
+        let skipped = converted.generated.skipped.len();
+
+        if args.diff {
+            let rewritten = Rewriter::replace_invocation(&input, span, code);
+            converted.stdout = Rewriter::unified_diff(&Self::diff_path(path)?, &input, &rewritten);
+        } else if skipped > 0 && (args.in_place || args.output.is_some()) {
+            // Writing would delete the benchmarks that failed to convert
+            converted.stderr += &format!(
+                "error: {} left unchanged, {} benchmark(s) fail to convert\n",
+                path.display(),
+                skipped
+            );
+        } else if args.in_place {
+            let rewritten = Rewriter::replace_invocation(&input, span, code);
+            let backup = Rewriter::write_in_place(path, &input, &rewritten)?;
+            converted.stderr += &format!(
+                "converted {} (backup at {})\n",
+                path.display(),
+                backup.display()
+            );
+        } else if let Some(output) = &args.output {
+            fs::write(output, code)
+                .with_context(|| format!("Failed to write {}", output.display()))?;
+        } else if args.report.is_none() {
+            converted.stdout = code.clone();
+        }
+        Ok(converted)
+    }
+
+    // Makes `path` relative to the current directory where possible, for reports.
+    fn display_path(path: &Path) -> PathBuf {
+        let relative = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf));
+        relative
+            .unwrap_or_else(|| path.to_path_buf())
+            .components()
+            .filter(|component| !matches!(component, std::path::Component::CurDir))
+            .collect()
+    }
+
+    // The path of `path` below the current directory, which diff headers need to apply
+    // with `git apply` or `patch -p1`.
+    fn diff_path(path: &Path) -> Result<PathBuf> {
+        let cwd = std::env::current_dir()
+            .and_then(|cwd| cwd.canonicalize())
+            .context("Failed to resolve the current directory")?;
+        let absolute = path
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", path.display()))?;
+        absolute
+            .strip_prefix(&cwd)
+            .map(Path::to_path_buf)
+            .map_err(|_| {
+                anyhow!(
+                    "--diff needs {} to be inside the current directory",
+                    path.display()
+                )
+            })
+    }
+
+    // Runs the lexer and writer over the `benchmarks!` invocation found in `input`.
+    fn generate(
+        path: &Path,
+        input: &str,
+        config: &Config,
+    ) -> Result<(Range<usize>, GeneratedModule)> {
+        let _file = debug_span!("file", path = %path.display()).entered();
+        let span = Lexer::invocation_span(input, config)
+            .map_err(|e| anyhow!(e))?
+            .ok_or_else(|| anyhow!("No benchmarks! invocation found in {}", path.display()))?;
+        debug!(?span, "located invocation");
+        let lines = Lexer::with_config(input[span.clone()].to_string(), config.clone())
+            .parse()
+            .map_err(|e| anyhow!(e))?;
+        let generated = Writer::generate_module(lines, config)?;
+        Ok((span, generated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_cli_parses_convert_with_output() {
+        let cli = Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "benchmarking.rs",
+            "--output",
+            "out.rs",
+        ]);
+        match cli.command {
+            Command::Convert(args) => {
+                assert_eq!(args.path, PathBuf::from("benchmarking.rs"));
+                assert_eq!(args.output, Some(PathBuf::from("out.rs")));
+                assert!(!args.in_place);
+            }
+            _ => panic!("expected convert"),
+        }
+    }
+
+    #[test]
+    fn test_verbosity_levels() {
+        let cli = Cli::parse_from(["benchmark-upgrader", "scan", ".", "-vv"]);
+        assert_eq!(cli.verbose, 2);
+        assert_eq!(Cli::log_level(0), None);
+        assert_eq!(Cli::log_level(1), Some(Level::DEBUG));
+        assert_eq!(Cli::log_level(cli.verbose), Some(Level::TRACE));
+    }
+
+    #[test]
+    fn test_cli_rejects_in_place_with_output() {
+        let result = Cli::try_parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "benchmarking.rs",
+            "--in-place",
+            "--output",
+            "out.rs",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_convert_in_place_then_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("benchmarking.rs");
+        let original = format!(
+            "use super::*;\n\n{}\n",
+            fs::read_to_string("src/fixtures/benchmark_v1.rs").unwrap()
+        );
+        fs::write(&path, &original).unwrap();
+
+        Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "--in-place",
+            path.to_str().unwrap(),
+        ])
+        .run()
+        .unwrap();
+        let converted = fs::read_to_string(&path).unwrap();
+        assert!(converted.starts_with("use super::*;\n\n#[benchmarks]\nmod benchmarks {"));
+        assert!(!converted.contains("benchmarks! {"));
+
+        Cli::parse_from(["benchmark-upgrader", "restore", path.to_str().unwrap()])
+            .run()
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_convert_in_place_keeps_file_with_skipped_benchmarks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("benchmarking.rs");
+        let original = r#"benchmarks! {
+	remark {
+		let l in 1 .. 10;
+	}: _(RawOrigin::Root, vec![0; l as usize])
+	reserved {
+		let type in 1 .. 10;
+	}: _(RawOrigin::Root)
+}
+"#;
+        fs::write(&path, original).unwrap();
+
+        let status = Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "--in-place",
+            path.to_str().unwrap(),
+        ])
+        .run()
+        .unwrap();
+        assert_eq!(status, ExitCode::FAILURE);
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(!Rewriter::backup_path(&path).exists());
+
+        let output = dir.path().join("out.rs");
+        let status = Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            path.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .run()
+        .unwrap();
+        assert_eq!(status, ExitCode::FAILURE);
+        assert!(!output.exists());
+
+        let result = Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "--in-place",
+            dir.path().to_str().unwrap(),
+        ])
+        .run();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "1 of 1 files failed to convert"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_convert_dir_in_place_then_restore_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = fs::read_to_string("src/fixtures/benchmark_v1.rs").unwrap();
+        let paths = [
+            dir.path().join("identity/src/benchmarking.rs"),
+            dir.path().join("proxy/src/benchmarking.rs"),
+        ];
+        for path in &paths {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, &fixture).unwrap();
+        }
+        let dir_arg = dir.path().to_str().unwrap();
+
+        Cli::parse_from(["benchmark-upgrader", "convert", "--in-place", dir_arg])
+            .run()
+            .unwrap();
+        for path in &paths {
+            assert!(fs::read_to_string(path)
+                .unwrap()
+                .starts_with("#[benchmarks]"));
+        }
+        assert!(Discovery::scan(dir.path(), &ConfigOverrides::default())
+            .unwrap()
+            .is_empty());
+
+        Cli::parse_from(["benchmark-upgrader", "restore", dir_arg])
+            .run()
+            .unwrap();
+        for path in &paths {
+            assert_eq!(fs::read_to_string(path).unwrap(), fixture);
+        }
+    }
+
+    #[test]
+    fn test_restore_dir_continues_after_failed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory in place of the original cannot be replaced by its backup
+        fs::create_dir(dir.path().join("a.rs")).unwrap();
+        for name in ["a.rs", "b.rs"] {
+            fs::write(Rewriter::backup_path(&dir.path().join(name)), "v1").unwrap();
+        }
+
+        let result = Cli::parse_from([
+            "benchmark-upgrader",
+            "restore",
+            dir.path().to_str().unwrap(),
+        ])
+        .run();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "1 of 2 files failed to restore"
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("b.rs")).unwrap(), "v1");
+        assert!(Rewriter::backup_path(&dir.path().join("a.rs")).exists());
+    }
+
+    #[test]
+    fn test_check_statuses() {
+        let dir = tempfile::tempdir().unwrap();
+        let upgraded = dir.path().join("upgraded.rs");
+        let convertible = dir.path().join("convertible.rs");
+        let failing = dir.path().join("failing.rs");
+        fs::write(&upgraded, "#[benchmarks]\nmod benchmarks {}\n").unwrap();
+        fs::write(
+            &convertible,
+            r#"benchmarks! {
+	add_registrar {
+		let account = T::Lookup::unlookup(account("registrar", 1, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+}"#,
+        )
+        .unwrap();
+        fs::write(
+            &failing,
+            "benchmarks! {\n\tadd_registrar {\n\t\tlet r in 1 .. 10;\n\t}\n}",
+        )
+        .unwrap();
+
+        let overrides = ConfigOverrides::default();
+        assert_eq!(
+            Cli::check_file(&upgraded, &overrides).0,
+            CheckStatus::Upgraded
+        );
+        assert_eq!(
+            Cli::check_file(&convertible, &overrides).0,
+            CheckStatus::Convertible
+        );
+        let (status, reason) = Cli::check_file(&failing, &overrides);
+        assert_eq!(status, CheckStatus::Failed);
+        assert_eq!(reason, "1 benchmark(s) fail to convert: add_registrar");
+        assert_eq!(
+            Cli::check(dir.path(), &overrides, None),
+            CheckStatus::Failed
+        );
+
+        let unbalanced = tempfile::tempdir().unwrap();
+        fs::write(
+            unbalanced.path().join("broken.rs"),
+            "benchmarks! {\n\ta {\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Cli::check(unbalanced.path(), &overrides, None),
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            Cli::check(&upgraded, &overrides, None),
+            CheckStatus::Upgraded
+        );
+    }
+
+    #[test]
+    fn test_display_path_is_relative() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            Cli::display_path(&cwd.join("src/benchmarking.rs")),
+            PathBuf::from("src/benchmarking.rs")
+        );
+        assert_eq!(
+            Cli::display_path(Path::new("./src/benchmarking.rs")),
+            PathBuf::from("src/benchmarking.rs")
+        );
+    }
+
+    #[test]
+    fn test_diff_path_is_below_current_directory() {
+        let cwd = std::env::current_dir().unwrap();
+        for path in [
+            cwd.join("src/main.rs"),
+            PathBuf::from("./src/../src/main.rs"),
+        ] {
+            assert_eq!(Cli::diff_path(&path).unwrap(), PathBuf::from("src/main.rs"));
+        }
+        let outside = tempfile::NamedTempFile::new().unwrap();
+        assert!(Cli::diff_path(outside.path()).is_err());
+    }
+
+    #[test]
+    fn test_filter_source_prints_only_the_module() {
+        let args = match Cli::parse_from(["benchmark-upgrader", "convert", "-"]).command {
+            Command::Convert(args) => args,
+            _ => panic!("expected convert"),
+        };
+        let input = r#"	// selection start
+benchmarks! {
+	add_registrar {
+		let account = T::Lookup::unlookup(account("registrar", 1, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+}
+"#;
+        let (output, result) = Cli::filter_source(&args, input);
+        assert!(result.unwrap().skipped.is_empty());
+        assert!(output.starts_with("#[benchmarks]\nmod benchmarks {"));
+        assert!(output.contains("#[extrinsic_call]"));
+
+        for input in [
+            "fn main() {}\n",
+            "benchmarks! {\n\ta {\n\t\tlet x in 10;\n\t}: _(RawOrigin::Root)\n}\n",
+            "benchmarks! {\n\ta {\n",
+            "benchmarks! {\n\ta {\n\t\tlet type in 1 .. 10;\n\t}: _(RawOrigin::Root)\n}\n",
+        ] {
+            let (output, _) = Cli::filter_source(&args, input);
+            assert_eq!(output, input);
+        }
+    }
+
+    #[test]
+    fn test_report_keeps_going_after_failed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = fs::read_to_string("src/fixtures/benchmark_v1.rs").unwrap();
+        fs::write(dir.path().join("a.rs"), &fixture).unwrap();
+        fs::write(
+            dir.path().join("b.rs"),
+            "benchmarks! {\n\ta {\n\t\tlet x in 10;\n\t}: _(RawOrigin::Root)\n}\n",
+        )
+        .unwrap();
+        let args = match Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "--report",
+            "json",
+            dir.path().to_str().unwrap(),
+        ])
+        .command
+        {
+            Command::Convert(args) => args,
+            _ => panic!("expected convert"),
+        };
+        assert_eq!(Cli::report(&args).unwrap(), ExitCode::FAILURE);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.rs")).unwrap(),
+            fixture
+        );
+    }
+
+    #[test]
+    fn test_par_map_keeps_order() {
+        let items: Vec<usize> = (0..64).collect();
+        let jobs = NonZeroUsize::new(4);
+        let doubled = Cli::par_map(jobs, &items, |item| item * 2).unwrap();
+        assert_eq!(
+            doubled,
+            items.iter().map(|item| item * 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_convert_dir_continues_after_failed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = fs::read_to_string("src/fixtures/benchmark_v1.rs").unwrap();
+        let failing = "benchmarks! {\n\ta {\n\t\tlet x in 10;\n\t}: _(RawOrigin::Root)\n}\n";
+        for (name, contents) in [
+            ("a.rs", fixture.as_str()),
+            ("b.rs", failing),
+            ("c.rs", &fixture),
+        ] {
+            fs::write(dir.path().join(name), contents).unwrap();
+        }
+
+        let result = Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "--in-place",
+            "--jobs",
+            "2",
+            dir.path().to_str().unwrap(),
+        ])
+        .run();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "1 of 3 files failed to convert"
+        );
+        for name in ["a.rs", "c.rs"] {
+            assert!(fs::read_to_string(dir.path().join(name))
+                .unwrap()
+                .starts_with("#[benchmarks]"));
+        }
+        assert_eq!(
+            fs::read_to_string(dir.path().join("b.rs")).unwrap(),
+            failing
+        );
+    }
+
+    #[test]
+    fn test_check_keeps_going_after_unreadable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let convertible = dir.path().join("benchmarking.rs");
+        let unreadable = dir.path().join("bin.rs");
+        fs::write(
+            &convertible,
+            "benchmarks! {\n\ta {\n\t}: _(RawOrigin::Root)\n}\n",
+        )
+        .unwrap();
+        fs::write(&unreadable, [0xff, 0xfe, 0x00]).unwrap();
+
+        let overrides = ConfigOverrides::default();
+        assert_eq!(
+            Cli::check(dir.path(), &overrides, None),
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            Cli::check_file(&convertible, &overrides).0,
+            CheckStatus::Convertible
+        );
+
+        let result = Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "--in-place",
+            dir.path().to_str().unwrap(),
+        ])
+        .run();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "1 of 2 files failed to convert"
+        );
+        assert!(fs::read_to_string(&convertible)
+            .unwrap()
+            .starts_with("#[benchmarks]"));
+    }
+
+    #[test]
+    fn test_convert_flags_override_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("src/benchmarking.rs");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            fs::read_to_string("src/fixtures/benchmark_v1.rs").unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(crate::config::CONFIG_FILE),
+            "module_name = \"bench\"\nindent = \"tabs\"\n",
+        )
+        .unwrap();
+
+        Cli::parse_from([
+            "benchmark-upgrader",
+            "convert",
+            "--in-place",
+            "--module-name",
+            "v2_benchmarks",
+            path.to_str().unwrap(),
+        ])
+        .run()
+        .unwrap();
+        let converted = fs::read_to_string(&path).unwrap();
+        assert!(converted.starts_with("#[benchmarks]\nmod v2_benchmarks {\n\tuse super::*;"));
+    }
+}
@@ -0,0 +1,300 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, ValueEnum};
+use proc_macro2::{TokenStream, TokenTree};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::debug;
+
+/// Name of the project configuration file, looked up from the input file upwards.
+pub const CONFIG_FILE: &str = ".benchmark-upgrader.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    Spaces,
+    Tabs,
+}
+
+/// A FRAME release, compared component by component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FrameVersion(pub u32, pub u32, pub u32);
+
+impl FrameVersion {
+    /// Benchmark functions return `Result<(), BenchmarkError>` from this release on. Older
+    /// v2 macros only accept functions returning `()`.
+    pub const RESULT_RETURN: FrameVersion = FrameVersion(1, 0, 0);
+}
+
+impl FromStr for FrameVersion {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let trimmed = input
+            .trim()
+            .trim_start_matches("polkadot-")
+            .trim_start_matches('v');
+        let mut parts = trimmed.split('.').map(|part| part.parse::<u32>());
+        let mut next = |required: bool| match parts.next() {
+            Some(Ok(part)) => Ok(part),
+            None if !required => Ok(0),
+            _ => Err(format!("Invalid FRAME version `{}`", input)),
+        };
+        let version = FrameVersion(next(true)?, next(false)?, next(false)?);
+        match parts.next() {
+            None => Ok(version),
+            Some(_) => Err(format!("Invalid FRAME version `{}`", input)),
+        }
+    }
+}
+
+impl TryFrom<String> for FrameVersion {
+    type Error = String;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        input.parse()
+    }
+}
+
+impl Display for FrameVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Output conventions for the generated code, read from `.benchmark-upgrader.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Name of the generated `#[benchmarks]` module.
+    pub module_name: String,
+    pub indent: IndentStyle,
+    /// Spaces per indentation level when `indent` is `spaces`.
+    pub indent_width: usize,
+    /// FRAME release the generated code has to compile against.
+    pub frame_version: FrameVersion,
+    /// Macros that are treated like `benchmarks!`, e.g. project specific wrappers. Paths
+    /// are matched by their final segment.
+    pub extra_macros: Vec<String>,
+    /// Macros that are treated like `benchmarks_instance_pallet!`, matched the same way.
+    pub extra_instance_macros: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            module_name: "benchmarks".to_string(),
+            indent: IndentStyle::Spaces,
+            indent_width: 4,
+            frame_version: FrameVersion::RESULT_RETURN,
+            extra_macros: Vec::new(),
+            extra_instance_macros: Vec::new(),
+        }
+    }
+}
+
+/// Command line flags that take precedence over the configuration file.
+#[derive(Debug, Clone, Default, Args)]
+#[command(next_help_heading = "Output conventions")]
+pub struct ConfigOverrides {
+    /// Name of the generated module [default: benchmarks]
+    #[arg(long, value_name = "NAME")]
+    pub module_name: Option<String>,
+    /// Indentation of the generated code [default: spaces]
+    #[arg(long, value_enum)]
+    pub indent: Option<IndentStyle>,
+    /// Spaces per indentation level [default: 4]
+    #[arg(long, value_name = "N")]
+    pub indent_width: Option<usize>,
+    /// FRAME release to target, e.g. `1.0` or `0.9.40` [default: 1.0.0]
+    #[arg(long, value_name = "VERSION")]
+    pub frame_version: Option<FrameVersion>,
+    /// Also convert invocations of this macro, may be repeated
+    #[arg(long = "extra-macro", value_name = "NAME")]
+    pub extra_macros: Vec<String>,
+    /// Also convert invocations of this instance macro, may be repeated
+    #[arg(long = "extra-instance-macro", value_name = "NAME")]
+    pub extra_instance_macros: Vec<String>,
+}
+
+impl Config {
+    /// Loads the configuration that applies to `input` and applies `overrides` on top.
+    pub fn load(input: &Path, overrides: &ConfigOverrides) -> Result<Self> {
+        let mut config = match Self::find(input) {
+            Some(path) => {
+                debug!(path = %path.display(), "loading configuration");
+                Self::from_file(&path)?
+            }
+            None => Self::default(),
+        };
+        config.apply(overrides);
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).map_err(|e| anyhow!("Invalid {}: {}", path.display(), e))
+    }
+
+    // Walks up from `input` to the closest directory containing a configuration file.
+    fn find(input: &Path) -> Option<PathBuf> {
+        let input = input.canonicalize().ok()?;
+        let start = if input.is_dir() {
+            input.as_path()
+        } else {
+            input.parent()?
+        };
+        start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+
+    fn apply(&mut self, overrides: &ConfigOverrides) {
+        if let Some(module_name) = &overrides.module_name {
+            self.module_name = module_name.clone();
+        }
+        if let Some(indent) = overrides.indent {
+            self.indent = indent;
+        }
+        if let Some(indent_width) = overrides.indent_width {
+            self.indent_width = indent_width;
+        }
+        if let Some(frame_version) = overrides.frame_version {
+            self.frame_version = frame_version;
+        }
+        self.extra_macros
+            .extend(overrides.extra_macros.iter().cloned());
+        self.extra_instance_macros
+            .extend(overrides.extra_instance_macros.iter().cloned());
+    }
+
+    /// Whether benchmark functions return `Result<(), BenchmarkError>`.
+    pub fn returns_result(&self) -> bool {
+        self.frame_version >= FrameVersion::RESULT_RETURN
+    }
+
+    /// Re-indents code printed with four spaces per level. Lines that continue a
+    /// multi-line literal are part of its value and are kept as they are.
+    pub fn indent_code(&self, code: &str) -> String {
+        if self.indent == IndentStyle::Spaces && self.indent_width == 4 {
+            return code.to_string();
+        }
+        let mut literal_lines = HashSet::new();
+        if let Ok(tokens) = code.parse() {
+            Self::literal_lines(tokens, &mut literal_lines);
+        }
+        code.lines()
+            .enumerate()
+            .map(|(index, line)| {
+                if literal_lines.contains(&(index + 1)) {
+                    return line.to_string();
+                }
+                let spaces = line.len() - line.trim_start_matches(' ').len();
+                let (levels, remainder) = (spaces / 4, spaces % 4);
+                let indent = match self.indent {
+                    IndentStyle::Tabs => "\t".repeat(levels),
+                    IndentStyle::Spaces => " ".repeat(levels * self.indent_width),
+                };
+                format!("{}{}{}", indent, " ".repeat(remainder), &line[spaces..])
+            })
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    // Collects the 1-based numbers of lines that start inside a literal.
+    fn literal_lines(tokens: TokenStream, lines: &mut HashSet<usize>) {
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) => Self::literal_lines(group.stream(), lines),
+                TokenTree::Literal(literal) => {
+                    let span = literal.span();
+                    lines.extend(span.start().line + 1..=span.end().line);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_version_parsing() {
+        assert_eq!("1.0".parse(), Ok(FrameVersion(1, 0, 0)));
+        assert_eq!("polkadot-v0.9.42".parse(), Ok(FrameVersion(0, 9, 42)));
+        assert!("1.x".parse::<FrameVersion>().is_err());
+        assert!("1.0.0.0".parse::<FrameVersion>().is_err());
+        assert!(FrameVersion(0, 9, 42) < FrameVersion::RESULT_RETURN);
+    }
+
+    #[test]
+    fn test_load_walks_up_and_applies_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("pallets/identity/src/benchmarking.rs");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "").unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            "module_name = \"bench\"\nindent = \"tabs\"\nframe_version = \"0.9.40\"\nextra_macros = [\"my_benchmarks\"]\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&file, &ConfigOverrides::default()).unwrap();
+        assert_eq!(config.module_name, "bench");
+        assert_eq!(config.indent, IndentStyle::Tabs);
+        assert!(!config.returns_result());
+
+        let overrides = ConfigOverrides {
+            module_name: Some("benches".to_string()),
+            extra_macros: vec!["other_benchmarks".to_string()],
+            extra_instance_macros: vec!["instance_benchmarks".to_string()],
+            ..Default::default()
+        };
+        let config = Config::load(&file, &overrides).unwrap();
+        assert_eq!(config.module_name, "benches");
+        assert_eq!(config.extra_macros, ["my_benchmarks", "other_benchmarks"]);
+        assert_eq!(config.extra_instance_macros, ["instance_benchmarks"]);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "modul_name = \"bench\"\n").unwrap();
+        assert!(Config::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_indent_code() {
+        let code = "mod benchmarks {\n    fn f() {\n        x();\n    }\n}\n";
+        let tabs = Config {
+            indent: IndentStyle::Tabs,
+            ..Default::default()
+        };
+        assert_eq!(
+            tabs.indent_code(code),
+            "mod benchmarks {\n\tfn f() {\n\t\tx();\n\t}\n}\n"
+        );
+        let two = Config {
+            indent_width: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            two.indent_code(code),
+            "mod benchmarks {\n  fn f() {\n    x();\n  }\n}\n"
+        );
+        let literal = "fn f() {\n    x(\"multi\n    line string\", r#\"raw\n        text\"#);\n}\n";
+        assert_eq!(
+            tabs.indent_code(literal),
+            "fn f() {\n\tx(\"multi\n    line string\", r#\"raw\n        text\"#);\n}\n"
+        );
+    }
+}
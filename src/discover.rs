@@ -0,0 +1,245 @@
+use crate::config::{Config, ConfigOverrides};
+use crate::lexer::{Lexer, LineKind};
+use crate::parser::block::BlockParser;
+use crate::rewrite::BACKUP_SUFFIX;
+use anyhow::{anyhow, Context, Result};
+use ignore::WalkBuilder;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A source file containing a v1 `benchmarks!` invocation.
+#[derive(Debug, Clone)]
+pub struct DiscoveredFile {
+    pub path: PathBuf,
+    /// Number of benchmarks in the invocation, `None` if the file could not be tokenized or
+    /// the lexer could not parse it.
+    pub benchmarks: Option<usize>,
+    /// Why the file could not be inspected, e.g. it is not valid UTF-8.
+    pub error: Option<String>,
+}
+
+pub struct Discovery;
+
+impl Discovery {
+    /// Walks `root`, honouring `.gitignore` files, and returns every Rust file that
+    /// invokes one of the v1 benchmark macros, sorted by path. Each file is inspected
+    /// with the configuration that applies to it. Files that cannot be read are listed
+    /// with their error instead of aborting the scan.
+    pub fn scan(root: &Path, overrides: &ConfigOverrides) -> Result<Vec<DiscoveredFile>> {
+        let mut found = Vec::new();
+        let is_rust = |path: &Path| path.extension().is_some_and(|ext| ext == "rs");
+        for path in Self::walk(root, true, is_rust)? {
+            let inspected = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))
+                .and_then(|source| Ok(Self::inspect(&source, Config::load(&path, overrides)?)));
+            match inspected {
+                Ok(Some(benchmarks)) => found.push(DiscoveredFile {
+                    path,
+                    benchmarks,
+                    error: None,
+                }),
+                Ok(None) => {}
+                Err(e) => found.push(DiscoveredFile {
+                    path,
+                    benchmarks: None,
+                    error: Some(format!("{:#}", e)),
+                }),
+            }
+        }
+        Ok(found)
+    }
+
+    /// Returns every backup left behind by an in-place conversion under `root`. Backups
+    /// are usually ignored by git, so `.gitignore` files are not honoured here.
+    pub fn backups(root: &Path) -> Result<Vec<PathBuf>> {
+        let backups = Self::walk(root, false, |path| {
+            path.to_string_lossy()
+                .ends_with(&format!(".rs{}", BACKUP_SUFFIX))
+        })?;
+        if backups.is_empty() {
+            return Err(anyhow!("No backups found under {}", root.display()));
+        }
+        Ok(backups)
+    }
+
+    // Returns `None` if there is no invocation, otherwise its benchmark count if it lexes.
+    fn inspect(source: &str, config: Config) -> Option<Option<usize>> {
+        // Cheap textual check before tokenizing the whole file. Every built-in macro name
+        // contains `benchmarks`, the finder decides whether one is actually invoked.
+        if !source.contains("benchmarks")
+            && !BlockParser::extra_macros(&config).any(|(name, _)| source.contains(name))
+        {
+            return None;
+        }
+        let span = match Lexer::invocation_span(source, &config) {
+            Ok(span) => span?,
+            // A file that does not tokenize is listed so that it is reported as failing
+            Err(_) => return Some(None),
+        };
+        let benchmarks = Lexer::with_config(source[span].to_string(), config)
+            .parse()
+            .ok()
+            .map(|lines| {
+                lines
+                    .iter()
+                    .filter(|line| matches!(line.kind, LineKind::Fn))
+                    .count()
+            });
+        Some(benchmarks)
+    }
+
+    fn walk(
+        root: &Path,
+        standard_filters: bool,
+        filter: impl Fn(&Path) -> bool,
+    ) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        // `.gitignore` files apply even when `root` is not inside a git checkout
+        let walker = WalkBuilder::new(root)
+            .standard_filters(standard_filters)
+            .require_git(false)
+            .build();
+        for entry in walker {
+            let entry = entry?;
+            let is_file = entry.file_type().is_some_and(|ty| ty.is_file());
+            if is_file && filter(entry.path()) {
+                paths.push(entry.into_path());
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_finds_v1_files_and_respects_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = fs::read_to_string("src/fixtures/benchmark_v1.rs").unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        };
+        write("identity/src/benchmarking.rs", &fixture);
+        write(
+            "collective/src/benchmarking.rs",
+            "benchmarks_instance_pallet! {\n\tclose {\n\t}: _(RawOrigin::Root)\n}\n",
+        );
+        write(
+            "collective/src/lib.rs",
+            "// no benchmarks! here\nfn main() {}\n",
+        );
+        write("target/debug/benchmarking.rs", &fixture);
+        write(".gitignore", "target/\n");
+
+        let found = Discovery::scan(dir.path(), &ConfigOverrides::default()).unwrap();
+        let paths: Vec<_> = found
+            .iter()
+            .map(|file| file.path.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("collective/src/benchmarking.rs"),
+                PathBuf::from("identity/src/benchmarking.rs"),
+            ]
+        );
+        assert_eq!(found[0].benchmarks, Some(1));
+    }
+
+    #[test]
+    fn test_scan_finds_extra_macros_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pallet/src/benchmarking.rs");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            "my_benchmarks! {\n\ta {\n\t}: _(RawOrigin::Root)\n}\n",
+        )
+        .unwrap();
+        assert!(Discovery::scan(dir.path(), &ConfigOverrides::default())
+            .unwrap()
+            .is_empty());
+
+        fs::write(
+            dir.path().join(crate::config::CONFIG_FILE),
+            "extra_macros = [\"my_benchmarks\"]\n",
+        )
+        .unwrap();
+        let found = Discovery::scan(dir.path(), &ConfigOverrides::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].benchmarks, Some(1));
+    }
+
+    #[test]
+    fn test_scan_finds_spaced_and_instance_macros() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("spaced.rs"),
+            "benchmarks !{\n\ta {\n\t}: _(RawOrigin::Root)\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("instance.rs"),
+            "my_instance_benchmarks! {\n\ta {\n\t}: _(RawOrigin::Root)\n}\n",
+        )
+        .unwrap();
+        let overrides = ConfigOverrides {
+            extra_instance_macros: vec!["my_instance_benchmarks".to_string()],
+            ..Default::default()
+        };
+        let found = Discovery::scan(dir.path(), &overrides).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|file| file.benchmarks == Some(1)));
+    }
+
+    #[test]
+    fn test_backups_ignore_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Discovery::backups(dir.path()).is_err());
+
+        let backup = dir.path().join("pallet/src/benchmarking.rs.v1.bak");
+        fs::create_dir_all(backup.parent().unwrap()).unwrap();
+        fs::write(&backup, "benchmarks! {}\n").unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.bak\n").unwrap();
+        assert_eq!(Discovery::backups(dir.path()).unwrap(), [backup]);
+    }
+
+    #[test]
+    fn test_scan_lists_files_that_fail_to_tokenize() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("broken.rs"),
+            "benchmarks! {\n\ta {\n\t}: _(RawOrigin::Root)\n",
+        )
+        .unwrap();
+        let found = Discovery::scan(dir.path(), &ConfigOverrides::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].benchmarks, None);
+    }
+
+    #[test]
+    fn test_scan_keeps_going_after_unreadable_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("bin.rs"), [0xff, 0xfe, 0x00]).unwrap();
+        fs::write(
+            dir.path().join("benchmarking.rs"),
+            "benchmarks! {\n\ta {\n\t}: _(RawOrigin::Root)\n}\n",
+        )
+        .unwrap();
+        let found = Discovery::scan(dir.path(), &ConfigOverrides::default()).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].benchmarks, Some(1));
+        assert!(found[0].error.is_none());
+        assert!(found[1].path.ends_with("bin.rs"));
+        assert!(found[1]
+            .error
+            .as_ref()
+            .unwrap()
+            .starts_with("Failed to read"));
+    }
+}
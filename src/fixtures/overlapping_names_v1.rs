@@ -0,0 +1,47 @@
+benchmarks! {
+	// `add_sub {` and `set_subs {` in a comment must not be taken for a benchmark
+	set_subs_new {
+		let s in 0 .. T::MaxSubAccounts::get();
+		let origin = RawOrigin::Signed(whitelisted_caller()).into();
+		let account = T::Lookup::unlookup(account("new", s, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+	verify {
+		ensure!(SubsOf::<T>::get(&account).1.len() as u32 == s, "set_subs_new verify");
+	}
+
+	set_subs_old {
+		let p in 0 .. T::MaxSubAccounts::get();
+		let origin = RawOrigin::Signed(whitelisted_caller()).into();
+		let account = T::Lookup::unlookup(account("old", p, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+	verify {
+		ensure!(SubsOf::<T>::get(&account).1.is_empty(), "set_subs_old verify");
+	}
+
+	set_subs {
+		let origin = RawOrigin::Signed(whitelisted_caller()).into();
+		let account = T::Lookup::unlookup(account("set_subs", 0, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+	verify {
+		ensure!(SubsOf::<T>::contains_key(&account), "set_subs verify");
+	}
+
+	rename_sub {
+		let origin = RawOrigin::Signed(whitelisted_caller()).into();
+		let _ = add_sub_accounts::<T>(&whitelisted_caller(), 1)?;
+		let account = T::Lookup::unlookup(account("rename_sub", 0, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+	verify {
+		ensure!(SuperOf::<T>::contains_key(&account), "rename_sub verify");
+	}
+
+	add_sub {
+		let origin = RawOrigin::Signed(whitelisted_caller()).into();
+		let account = T::Lookup::unlookup(account("add_sub", 0, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+	verify {
+		ensure!(SuperOf::<T>::contains_key(&account), "add_sub verify");
+	}
+
+	impl_benchmark_test_suite!(Identity, crate::tests::new_test_ext(), crate::tests::Test);
+}
@@ -1,16 +1,25 @@
-use crate::parser::block::BlockParser;
+use crate::config::Config;
 use crate::parser::param::BenchmarkParameter;
-use syn::{parse_str, Item};
+use crate::parser::{benchmark::BenchmarkParser, block::BlockParser};
+use proc_macro2::{LineColumn, TokenStream};
+use std::ops::Range;
+use tracing::{debug, trace};
 
 #[derive(Debug, Clone)]
 pub enum LineKind {
     Mod,
     Fn,
+    /// An argument of the next benchmark's `#[benchmark(...)]`, e.g. `extra`.
+    Attribute,
+    /// The `_ { ... }` block declaring components shared by all benchmarks.
+    Common,
+    /// The predicates of a `where_clause { where ... }` block.
+    WhereClause,
     FnParam,
+    /// A part of the current benchmark that does not parse, with the reason as content.
+    Error,
     Verify,
-    Ensure,
     Extrinsic,
-    Content,
     ImplMacro,
 }
 
@@ -23,40 +32,61 @@ pub struct BenchmarkLine {
     pub fn_body: Option<String>,
 }
 
-pub struct Lexer(pub(crate) String);
+pub struct Lexer {
+    pub(crate) input: String,
+    pub(crate) config: Config,
+}
 
 impl Lexer {
     pub fn new(input: String) -> Self {
-        Self(input)
+        Self::with_config(input, Config::default())
+    }
+
+    pub fn with_config(input: String, config: Config) -> Self {
+        Self { input, config }
     }
 
+    /// Parses the invocation into the lines the writer consumes, following its token
+    /// structure rather than its line breaks.
     pub fn parse(&self) -> Result<Vec<BenchmarkLine>, String> {
-        let lines: Vec<&str> = self.0.split("\n").collect();
-        let mut blocks: Vec<BenchmarkLine> = Vec::new();
-        for line in lines {
-            match BlockParser::dispatch(line, self.clone()) {
-                Ok(line) => {
-                    let benchmark_line = BenchmarkLine {
-                        head: line.head,
-                        kind: line.kind,
-                        content: line.content,
-                        param_content: line.param_content,
-                        fn_body: line.fn_body,
-                    };
-                    //println!("/n: {:?}", benchmark_line);
-                    blocks.push(benchmark_line);
-                }
-                Err(e) => {
-                    return Err(format!("Parsing failed: {:?}", e));
-                }
-            }
-            //println!("blocks: {:?}", blocks);
+        let lines = BenchmarkParser::parse(&self.input, &self.config)
+            .map_err(|e| format!("Parsing failed: {:#}", e))?;
+        for line in &lines {
+            trace!(kind = ?line.kind, head = ?line.head, "parsed line");
         }
-        if blocks.is_empty() {
-            Err("No blocks parsed".to_string())
-        } else {
-            Ok(blocks)
+        debug!(lines = lines.len(), "parsed invocation");
+        Ok(lines)
+    }
+
+    /// Locates the `benchmarks!` invocation in a source file and returns its byte range,
+    /// from the macro path up to and including the closing brace. Macros listed in
+    /// `extra_macros` and `extra_instance_macros` are matched as well.
+    pub fn invocation_span(source: &str, config: &Config) -> Result<Option<Range<usize>>, String> {
+        let tokens: TokenStream = source
+            .parse()
+            .map_err(|e| format!("Tokenizing failed: {:?}", e))?;
+        Ok(
+            BlockParser::find_invocation(tokens, config).map(|invocation| {
+                Self::byte_offset(source, invocation.start)
+                    ..Self::byte_offset(source, invocation.body.span().end())
+            }),
+        )
+    }
+
+    // Converts a 1-based line and 0-based character column into a byte offset.
+    pub(crate) fn byte_offset(source: &str, position: LineColumn) -> usize {
+        let mut offset = 0;
+        for (number, line) in source.split_inclusive('\n').enumerate() {
+            if number + 1 == position.line {
+                return offset
+                    + line
+                        .char_indices()
+                        .nth(position.column)
+                        .map_or(line.len(), |(index, _)| index);
+            }
+            offset += line.len();
         }
+        source.len()
     }
 }
 
@@ -76,7 +106,8 @@ mod tests {
 	}: _<T::RuntimeOrigin>(origin, account)
 	verify {
 		ensure!(Registrars::<T>::get().len() as u32 == r + 1, "Registrars not added.");
-	}"#;
+	}
+}"#;
 
         let l = Lexer::new(input.to_string());
         let parsed = l.parse().unwrap();
@@ -84,4 +115,54 @@ mod tests {
             println!("\n{:?}\n", block);
         }
     }
+
+    #[test]
+    fn test_lexer_and_writer_are_thread_safe() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Lexer>();
+        assert_send_sync::<BenchmarkLine>();
+        assert_send_sync::<crate::writer::Writer>();
+        assert_send_sync::<crate::writer::GeneratedModule>();
+    }
+
+    #[test]
+    fn test_invocation_span_skips_surrounding_code() {
+        let source = r#"use super::*;
+
+fn add_registrars<T: Config>(r: u32) -> Result<(), &'static str> {
+	Ok(())
+}
+
+benchmarks! {
+	add_registrar {
+		let s = "}";
+	}: _(RawOrigin::Root)
+}
+
+fn trailing() {}
+"#;
+        let span = Lexer::invocation_span(source, &Config::default())
+            .unwrap()
+            .unwrap();
+        assert!(source[span.clone()].starts_with("benchmarks! {"));
+        assert!(source[span].ends_with("_(RawOrigin::Root)\n}"));
+    }
+
+    #[test]
+    fn test_invocation_span_includes_macro_path() {
+        let source = "use super::*;\n\n::frame_benchmarking::v1::benchmarks_instance! {\n\ta {\n\t}: _(RawOrigin::Root)\n}\n";
+        let span = Lexer::invocation_span(source, &Config::default())
+            .unwrap()
+            .unwrap();
+        assert!(source[span].starts_with("::frame_benchmarking::v1::benchmarks_instance! {"));
+    }
+
+    #[test]
+    fn test_invocation_span_without_benchmarks() {
+        let source = "use frame_benchmarking::{benchmarks, whitelisted_caller};\nfn main() {}";
+        assert_eq!(
+            Lexer::invocation_span(source, &Config::default()).unwrap(),
+            None
+        );
+    }
 }
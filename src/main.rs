@@ -1,25 +1,18 @@
-use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
-use quote::{format_ident, quote, ToTokens};
-use regex;
-use std::fmt::{Display, Formatter};
-use std::{fmt, fs, iter::Peekable, result::Result};
-use syn::parse::Parser;
-use syn::{
-    braced,
-    parse::{Parse, ParseStream, Result as ParseResult},
-    parse2, parse_quote,
-    punctuated::Punctuated,
-    token::Semi,
-    visit_mut::VisitMut,
-    Attribute, Block, Expr, File, FnArg, Generics, Ident, Item, ItemFn, ItemMacro, ItemMod,
-    ReturnType, Signature, Stmt, Token, Visibility,
-};
+use anyhow::Result;
+use clap::Parser;
+use std::process::ExitCode;
 
+pub mod cli;
+pub mod config;
+pub mod discover;
 pub mod lexer;
 pub mod parser;
+pub mod report;
+pub mod rewrite;
 pub mod writer;
 
-use lexer::Lexer;
-use writer::Writer;
-
-fn main() {}
+fn main() -> Result<ExitCode> {
+    let cli = cli::Cli::parse();
+    cli.init_logging();
+    cli.run()
+}
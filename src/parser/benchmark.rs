@@ -1,10 +1,633 @@
-use nom::IResult;
+use crate::config::Config;
+use crate::lexer::{BenchmarkLine, Lexer, LineKind};
+use crate::parser::{
+    block::BlockParser,
+    param::{BodyPart, ParamParser},
+};
+use anyhow::{anyhow, Result};
+use proc_macro2::{Delimiter, Group, Ident, LineColumn, TokenStream, TokenTree};
 
+/// Reads a `benchmarks!` invocation as token trees. Bodies and calls are sliced out of the
+/// source by span, so line breaks and formatting have no influence on the structure.
 pub struct BenchmarkParser;
 
-pub enum ParsedElement {
-    Block(String),
-    FnParam(String),
+impl BenchmarkParser {
+    pub fn parse(source: &str, config: &Config) -> Result<Vec<BenchmarkLine>> {
+        let tokens: TokenStream = source
+            .parse()
+            .map_err(|e| anyhow!("Tokenizing failed: {}", e))?;
+        let invocation = BlockParser::find_invocation(tokens, config)
+            .ok_or_else(|| anyhow!("No benchmarks! invocation found"))?;
+
+        let mut lines = vec![Self::line(
+            LineKind::Mod,
+            Some(invocation.kind.name().to_string()),
+        )];
+        let items: Vec<TokenTree> = invocation.body.stream().into_iter().collect();
+        let mut i = 0;
+        while i < items.len() {
+            i = Self::item(source, &items, i, &mut lines)?;
+        }
+        Ok(lines)
+    }
+
+    // Parses the item starting at `tokens[i]` and returns the index after it.
+    fn item(
+        source: &str,
+        tokens: &[TokenTree],
+        i: usize,
+        lines: &mut Vec<BenchmarkLine>,
+    ) -> Result<usize> {
+        match (&tokens[i], tokens.get(i + 1), tokens.get(i + 2)) {
+            (TokenTree::Punct(pound), Some(TokenTree::Group(group)), _)
+                if pound.as_char() == '#' && group.delimiter() == Delimiter::Bracket =>
+            {
+                // Attributes other than the benchmark options are not carried over
+                if let Some(argument) = Self::attribute(source, group) {
+                    let mut line = Self::line(LineKind::Attribute, None);
+                    line.content = Some(argument);
+                    lines.push(line);
+                }
+                Ok(i + 2)
+            }
+            (
+                TokenTree::Ident(ident),
+                Some(TokenTree::Punct(bang)),
+                Some(TokenTree::Group(args)),
+            ) if ident == "impl_benchmark_test_suite" && bang.as_char() == '!' => {
+                let (end, next) = match tokens.get(i + 3) {
+                    Some(TokenTree::Punct(semi)) if semi.as_char() == ';' => {
+                        (semi.span().end(), i + 4)
+                    }
+                    _ => (args.span().end(), i + 3),
+                };
+                let mut content = Self::slice(source, ident.span().start(), end).to_string();
+                if args.delimiter() != Delimiter::Brace && !content.ends_with(';') {
+                    content.push(';');
+                }
+                let mut line = Self::line(LineKind::ImplMacro, None);
+                line.content = Some(content);
+                lines.push(line);
+                Ok(next)
+            }
+            (TokenTree::Ident(name), Some(TokenTree::Group(body)), _)
+                if name == "where_clause" && Self::where_predicates(source, body).is_some() =>
+            {
+                let mut line = Self::line(LineKind::WhereClause, None);
+                line.content = Self::where_predicates(source, body).map(str::to_string);
+                lines.push(line);
+                Ok(i + 2)
+            }
+            (TokenTree::Ident(name), Some(TokenTree::Group(body)), _)
+                if name == "_" && body.delimiter() == Delimiter::Brace =>
+            {
+                lines.push(Self::line(LineKind::Common, Some(name.to_string())));
+                Self::setup(source, body, lines);
+                Ok(i + 2)
+            }
+            (TokenTree::Ident(name), Some(TokenTree::Group(body)), _)
+                if body.delimiter() == Delimiter::Brace =>
+            {
+                Self::benchmark(source, tokens, i, name, body, lines)
+            }
+            (token, _, _) => Err(anyhow!(
+                "Unexpected `{}` at line {} of the invocation",
+                token,
+                token.span().start().line
+            )),
+        }
+    }
+
+    // Turns `#[extra]`, `#[skip_meta]` and `#[pov_mode = ...]` into their v2 argument.
+    fn attribute(source: &str, group: &Group) -> Option<String> {
+        let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+        match tokens.as_slice() {
+            [TokenTree::Ident(name)] if name == "extra" || name == "skip_meta" => {
+                Some(name.to_string())
+            }
+            [TokenTree::Ident(name), TokenTree::Punct(eq), _, ..]
+                if name == "pov_mode" && eq.as_char() == '=' =>
+            {
+                let value = Self::slice(source, eq.span().end(), group.span_close().start());
+                Some(format!("pov_mode = {}", value.trim()))
+            }
+            _ => None,
+        }
+    }
+
+    // The source text after `where` in a `where_clause { where ... }` body.
+    fn where_predicates<'a>(source: &'a str, body: &Group) -> Option<&'a str> {
+        match body.stream().into_iter().next() {
+            Some(TokenTree::Ident(keyword))
+                if keyword == "where" && body.delimiter() == Delimiter::Brace =>
+            {
+                let predicates =
+                    Self::slice(source, keyword.span().end(), body.span_close().start());
+                Some(predicates.trim())
+            }
+            _ => None,
+        }
+    }
+
+    // Parses `name { setup }: call verify { ... }`, where the call and `verify` are optional.
+    fn benchmark(
+        source: &str,
+        tokens: &[TokenTree],
+        i: usize,
+        name: &Ident,
+        body: &Group,
+        lines: &mut Vec<BenchmarkLine>,
+    ) -> Result<usize> {
+        let mut line = Self::line(LineKind::Fn, Some(name.to_string()));
+        line.fn_body = Some(Self::inner(source, body).to_string());
+        lines.push(line);
+        Self::setup(source, body, lines);
+
+        let mut i = i + 2;
+        if matches!(tokens.get(i), Some(TokenTree::Punct(colon)) if colon.as_char() == ':') {
+            let end = Self::call_end(tokens, i + 1)
+                .ok_or_else(|| anyhow!("Benchmark `{}` has no call after `:`", name))?;
+            let mut line = Self::line(LineKind::Extrinsic, None);
+            line.content = Some(
+                Self::slice(
+                    source,
+                    tokens[i + 1].span().start(),
+                    tokens[end].span().end(),
+                )
+                .to_string(),
+            );
+            lines.push(line);
+            i = end + 1;
+        }
+        if let (Some(TokenTree::Ident(verify)), Some(TokenTree::Group(body))) =
+            (tokens.get(i), tokens.get(i + 1))
+        {
+            if verify == "verify" && body.delimiter() == Delimiter::Brace {
+                let mut line = Self::line(LineKind::Verify, Some(verify.to_string()));
+                line.fn_body = Some(Self::inner(source, body).to_string());
+                lines.push(line);
+                i += 2;
+            }
+        }
+        Ok(i)
+    }
+
+    // Emits a parameter line per component of a benchmark body, or an error line for a
+    // component that does not parse. The statements around them are split with syn by
+    // the writer, which reads the body from `fn_body`.
+    fn setup(source: &str, body: &Group, lines: &mut Vec<BenchmarkLine>) {
+        for part in ParamParser::split_body(body.stream()) {
+            let BodyPart::Component(component) = part else {
+                continue;
+            };
+            let (Some(first), Some(last)) = (component.first(), component.last()) else {
+                continue;
+            };
+            let text = Self::slice(source, first.span().start(), last.span().end());
+            match ParamParser::let_declaration(text) {
+                Ok((_, param)) => {
+                    let mut line = Self::line(LineKind::FnParam, None);
+                    line.param_content = Some(param);
+                    lines.push(line);
+                }
+                Err(e) => {
+                    let mut line = Self::line(LineKind::Error, None);
+                    line.content = Some(format!("Invalid component `{}`: {:?}", text, e));
+                    lines.push(line);
+                }
+            }
+        }
+    }
+
+    // Index of the last token of the call starting at `start`: a `{ ... }` block, or
+    // everything up to the argument list of `name(...)`.
+    fn call_end(tokens: &[TokenTree], start: usize) -> Option<usize> {
+        for (index, token) in tokens.iter().enumerate().skip(start) {
+            match token {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+                    return Some(index)
+                }
+                // Only a block right after `:` belongs to this benchmark
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                    return (index == start).then_some(index)
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn inner<'a>(source: &'a str, group: &Group) -> &'a str {
+        Self::slice(source, group.span_open().end(), group.span_close().start())
+    }
+
+    fn slice(source: &str, start: LineColumn, end: LineColumn) -> &str {
+        &source[Lexer::byte_offset(source, start)..Lexer::byte_offset(source, end)]
+    }
+
+    fn line(kind: LineKind, head: Option<String>) -> BenchmarkLine {
+        BenchmarkLine {
+            head,
+            kind,
+            content: None,
+            param_content: None,
+            fn_body: None,
+        }
+    }
 }
 
-impl BenchmarkParser {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<BenchmarkLine> {
+        BenchmarkParser::parse(source, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_invocation_kind() {
+        let lines = parse("benchmarks! {}");
+        assert!(matches!(lines[0].kind, LineKind::Mod));
+        assert_eq!(lines[0].head.as_deref(), Some("benchmarks"));
+
+        let lines = parse("benchmarks_instance_pallet! {}");
+        assert_eq!(lines[0].head.as_deref(), Some("benchmarks_instance_pallet"));
+        assert!(BenchmarkParser::parse("fn main() {}", &Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_benchmark_independent_of_line_breaks() {
+        let compact = "benchmarks! { propose_proposed_with_new_name { let p in 1 .. 100; let caller = whitelisted_caller(); }: _(RawOrigin::Signed(caller)) verify { ensure!(true, \"ok\"); } }";
+        let spread = r#"benchmarks!
+{
+	propose_proposed_with_new_name
+	{
+		let p
+			in 1 .. 100;
+		let caller =
+			whitelisted_caller();
+	}
+	:
+	_(
+		RawOrigin::Signed(caller)
+	)
+	verify
+	{
+		ensure!(true, "ok");
+	}
+}"#;
+        for source in [compact, spread] {
+            let lines = parse(source);
+            let kinds: Vec<String> = lines
+                .iter()
+                .map(|line| format!("{:?}", line.kind))
+                .collect();
+            assert_eq!(kinds, ["Mod", "Fn", "FnParam", "Extrinsic", "Verify"]);
+            assert_eq!(
+                lines[1].head.as_deref(),
+                Some("propose_proposed_with_new_name")
+            );
+            let param = lines[2].param_content.as_ref().unwrap();
+            assert_eq!(
+                (
+                    param.name.as_str(),
+                    param.range_start.as_str(),
+                    param.range_end.as_str()
+                ),
+                ("p", "1", "100")
+            );
+            assert!(lines[1]
+                .fn_body
+                .as_ref()
+                .unwrap()
+                .contains("whitelisted_caller()"));
+            let call: String = lines[3]
+                .content
+                .as_ref()
+                .unwrap()
+                .split_whitespace()
+                .collect();
+            assert_eq!(call, "_(RawOrigin::Signed(caller))");
+            assert_eq!(lines[4].head.as_deref(), Some("verify"));
+            assert_eq!(
+                lines[4].fn_body.as_ref().unwrap().trim(),
+                "ensure!(true, \"ok\");"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_block_call_and_test_suite() {
+        let lines = parse(
+            r#"benchmarks! {
+	#[extra]
+	on_initialize {
+		let b in 1 .. 10;
+	}: {
+		Pallet::<T>::on_initialize(b.into());
+	}
+	impl_benchmark_test_suite!(Pallet, crate::tests::new_test_ext(), crate::tests::Test)
+}"#,
+        );
+        assert!(matches!(lines[1].kind, LineKind::Attribute));
+        assert_eq!(lines[2].head.as_deref(), Some("on_initialize"));
+        assert!(lines[4].content.as_ref().unwrap().starts_with('{'));
+        assert!(matches!(lines[5].kind, LineKind::ImplMacro));
+        assert_eq!(
+            lines[5].content.as_deref(),
+            Some("impl_benchmark_test_suite!(Pallet, crate::tests::new_test_ext(), crate::tests::Test);")
+        );
+    }
+
+    #[test]
+    fn test_parse_benchmark_attributes() {
+        let lines = parse(
+            r#"benchmarks! {
+	#[extra]
+	#[skip_meta]
+	#[pov_mode = MaxEncodedLen {
+		Pallet::Storage: Measured
+	}]
+	#[cfg(test)]
+	remark {
+	}: _(RawOrigin::Root)
+}"#,
+        );
+        let attributes: Vec<&str> = lines
+            .iter()
+            .filter(|line| matches!(line.kind, LineKind::Attribute))
+            .filter_map(|line| line.content.as_deref())
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                "extra",
+                "skip_meta",
+                "pov_mode = MaxEncodedLen {\n\t\tPallet::Storage: Measured\n\t}"
+            ]
+        );
+        assert_eq!(lines[4].head.as_deref(), Some("remark"));
+    }
+
+    #[test]
+    fn test_parse_keeps_nested_bodies_intact() {
+        let setup = r#"
+		let s in 1 .. 10;
+		let caller = {
+			// a comment with an unbalanced }
+			let braces = "}}{";
+			let brace = '}';
+			let raw = r"}";
+			let items = [0u8; 3].iter().map(|i| { i + 1 }).collect::<Vec<_>>();
+			for i in 0..s {
+				match i {
+					0 => { /* } */ }
+					_ => (),
+				}
+			}
+			whitelisted_caller()
+		};
+	"#;
+        let verify = r#"
+		if let Some(x) = Foo::<T>::get() { ensure!(x == '{', "}"); }
+	"#;
+        let source = format!(
+            "benchmarks! {{\n\ta {{{}}}: _(RawOrigin::Signed(caller))\n\tverify {{{}}}\n}}",
+            setup, verify
+        );
+        let lines = parse(&source);
+        assert_eq!(lines[1].fn_body.as_deref(), Some(setup));
+        assert!(matches!(lines[2].kind, LineKind::FnParam));
+        assert!(matches!(lines[3].kind, LineKind::Extrinsic));
+        assert_eq!(lines[4].fn_body.as_deref(), Some(verify));
+    }
+
+    #[test]
+    fn test_parse_fixture_set_identity_setup() {
+        let source = std::fs::read_to_string("src/fixtures/benchmark_v1.rs").unwrap();
+        let lines = parse(&source);
+        let index = lines
+            .iter()
+            .position(|line| line.head.as_deref() == Some("set_identity"))
+            .unwrap();
+        let body = lines[index].fn_body.as_ref().unwrap();
+        assert!(body.contains("for i in 0..r {"));
+        assert!(body.contains("T::Hashing::hash_of(&initial_info),"));
+        assert!(body.trim_end().ends_with("caller\n\t\t};"));
+        let verify = lines[index..]
+            .iter()
+            .find(|line| matches!(line.kind, LineKind::Verify))
+            .unwrap();
+        assert_eq!(
+            verify.fn_body.as_ref().unwrap().trim(),
+            "assert_last_event::<T>(Event::<T>::IdentitySet { who: caller }.into());"
+        );
+    }
+
+    #[test]
+    fn test_parse_headers_only_at_top_level() {
+        let lines = parse(
+            r#"benchmarks! {
+	transfer_for_all {
+		let r in 1 .. 10;
+		let caller = {
+			whitelisted_caller()
+		};
+		if r > 5 {
+			fund::<T>(&caller)?;
+		}
+		let amount = match r {
+			1 => 10u32,
+			_ => 100u32,
+		};
+	}: _(RawOrigin::Signed(caller), amount)
+	transfer {
+		let caller = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller))
+}"#,
+        );
+        let headers: Vec<&str> = lines
+            .iter()
+            .filter(|line| matches!(line.kind, LineKind::Fn))
+            .filter_map(|line| line.head.as_deref())
+            .collect();
+        assert_eq!(headers, ["transfer_for_all", "transfer"]);
+        let body = lines[1].fn_body.as_deref().unwrap();
+        assert!(body.contains("if r > 5 {"));
+        assert!(body.trim_end().ends_with("_ => 100u32,\n\t\t};"));
+        assert!(matches!(lines[2].kind, LineKind::FnParam));
+        assert!(matches!(lines[3].kind, LineKind::Extrinsic));
+    }
+
+    #[test]
+    fn test_parse_where_clause() {
+        let lines = parse(
+            r#"benchmarks! {
+	where_clause {
+		where
+			T: pallet_balances::Config,
+			T::Balance: From<u32>,
+	}
+	transfer {
+		let caller = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller))
+}"#,
+        );
+        assert!(matches!(lines[1].kind, LineKind::WhereClause));
+        assert_eq!(
+            lines[1].content.as_deref(),
+            Some("T: pallet_balances::Config,\n\t\t\tT::Balance: From<u32>,")
+        );
+        let headers: Vec<&str> = lines
+            .iter()
+            .filter(|line| matches!(line.kind, LineKind::Fn))
+            .filter_map(|line| line.head.as_deref())
+            .collect();
+        assert_eq!(headers, ["transfer"]);
+    }
+
+    #[test]
+    fn test_parse_where_clause_after_comment() {
+        let lines = parse(
+            r#"benchmarks! {
+	where_clause {
+		// Bounds für den Saldo
+		/* weitere Bounds */ where T: pallet_balances::Config,
+	}
+}"#,
+        );
+        assert!(matches!(lines[1].kind, LineKind::WhereClause));
+        assert_eq!(
+            lines[1].content.as_deref(),
+            Some("T: pallet_balances::Config,")
+        );
+    }
+
+    #[test]
+    fn test_parse_common_components() {
+        let lines = parse(
+            r#"benchmarks! {
+	_ {
+		let l in 1 .. MAX_LENGTH => ();
+	}
+	remark {
+		let l in _ .. _ => ();
+	}: _(RawOrigin::Signed(whitelisted_caller()), vec![0; l as usize])
+}"#,
+        );
+        let kinds: Vec<String> = lines
+            .iter()
+            .map(|line| format!("{:?}", line.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            ["Mod", "Common", "FnParam", "Fn", "FnParam", "Extrinsic"]
+        );
+        let common = lines[2].param_content.as_ref().unwrap();
+        assert_eq!(common.range_end, "MAX_LENGTH");
+        assert!(common.setup.is_none());
+        assert_eq!(lines[4].param_content.as_ref().unwrap().range_start, "_");
+    }
+
+    #[test]
+    fn test_parse_prefix_overlapping_names() {
+        let source = std::fs::read_to_string("src/fixtures/overlapping_names_v1.rs").unwrap();
+        let lines = parse(&source);
+        let benchmarks: Vec<(&str, &str)> = lines
+            .iter()
+            .filter(|line| matches!(line.kind, LineKind::Fn))
+            .map(|line| {
+                (
+                    line.head.as_deref().unwrap(),
+                    line.fn_body.as_deref().unwrap(),
+                )
+            })
+            .collect();
+        let expected = [
+            ("set_subs_new", "account(\"new\""),
+            ("set_subs_old", "account(\"old\""),
+            ("set_subs", "account(\"set_subs\""),
+            ("rename_sub", "account(\"rename_sub\""),
+            ("add_sub", "account(\"add_sub\""),
+        ];
+        assert_eq!(benchmarks.len(), expected.len());
+        for ((name, body), (expected_name, seed)) in benchmarks.into_iter().zip(expected) {
+            assert_eq!(name, expected_name);
+            assert!(body.contains(seed), "{}: {}", name, body);
+        }
+    }
+
+    #[test]
+    fn test_parse_marks_invalid_component() {
+        let lines = parse(
+            "benchmarks! { a { let x in 10; }: _(RawOrigin::Root) b { }: _(RawOrigin::Root) }",
+        );
+        let kinds: Vec<String> = lines
+            .iter()
+            .map(|line| format!("{:?}", line.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            ["Mod", "Fn", "Error", "Extrinsic", "Fn", "Extrinsic"]
+        );
+        assert!(lines[2]
+            .content
+            .as_ref()
+            .unwrap()
+            .starts_with("Invalid component `let x in 10"));
+    }
+
+    #[test]
+    fn test_dispatch_should_call_benchmarks() {
+        let lines = parse("benchmarks! {}");
+        assert_eq!(lines[0].head, Some("benchmarks".to_string()));
+    }
+
+    #[test]
+    fn test_dispatch_should_call_function() {
+        let lines = parse("benchmarks! { propose_proposed { }: _(RawOrigin::Root) }");
+        assert_eq!(lines[1].head, Some("propose_proposed".to_string()));
+    }
+
+    #[test]
+    fn test_parse_valid_function_call() {
+        let lines = parse("benchmarks! { propose_proposed { }: _(RawOrigin::Root) }");
+        assert!(matches!(lines[2].kind, LineKind::Extrinsic));
+        assert_eq!(lines[2].content.as_deref(), Some("_(RawOrigin::Root)"));
+    }
+
+    #[test]
+    fn test_parse_verify_function_call() {
+        let lines = parse(
+            "benchmarks! { propose_proposed { }: _(RawOrigin::Root) verify { ensure!(true, \"ok\"); } }",
+        );
+        assert!(matches!(lines[3].kind, LineKind::Verify));
+        assert_eq!(lines[3].head.as_deref(), Some("verify"));
+        assert_eq!(
+            lines[3].fn_body.as_deref().map(str::trim),
+            Some("ensure!(true, \"ok\");")
+        );
+    }
+
+    #[test]
+    fn test_parse_function_call_with_whitespace() {
+        let lines = parse("benchmarks! {\n   propose_proposed   {\n   }  :  _(RawOrigin::Root)\n}");
+        assert_eq!(lines[1].head.as_deref(), Some("propose_proposed"));
+        assert_eq!(lines[2].content.as_deref(), Some("_(RawOrigin::Root)"));
+    }
+
+    #[test]
+    fn test_parse_function_call_with_new_name() {
+        let lines = parse(
+            "benchmarks! { propose_proposed_with_new_name { }: propose_proposed(RawOrigin::Root, 1) }",
+        );
+        assert_eq!(
+            lines[1].head.as_deref(),
+            Some("propose_proposed_with_new_name")
+        );
+        assert_eq!(
+            lines[2].content.as_deref(),
+            Some("propose_proposed(RawOrigin::Root, 1)")
+        );
+    }
+}
@@ -1,208 +1,293 @@
-use crate::lexer::{BenchmarkLine, Lexer, LineKind};
-use crate::parser::{extrinsic::ExtrinsicCall, param::ParamParser};
+use crate::config::Config;
+use crate::lexer::Lexer;
+use crate::parser::{
+    extrinsic::ExtrinsicCall,
+    param::{BenchmarkParameter, BodyPart, ParamParser, ParamWriter},
+};
 use anyhow::{anyhow, Result};
-use nom::combinator::{cut, map_parser, not, peek, recognize};
-use nom::error::context;
-use nom::multi::{many0, many0_count, many1, separated_list0, separated_list1};
-use nom::sequence::{delimited, pair, separated_pair, terminated, tuple};
 use nom::{
-    branch::alt,
-    bytes::complete::{tag, take_until, take_while},
-    character::complete::{alpha1, char, multispace0, multispace1},
-    combinator::map,
-    sequence::preceded,
-    IResult,
+    branch::alt, bytes::complete::tag, character::complete::multispace0, combinator::map,
+    sequence::preceded, IResult,
 };
+use proc_macro2::{Delimiter, Group, Ident, LineColumn, Spacing, TokenStream, TokenTree};
 use quote::quote;
-use syn::{parse_quote, Block, Item, ItemFn, ItemMod, Stmt, parse2, ExprMacro, Expr};
-use proc_macro2::TokenStream;
-use syn::punctuated::Pair::Punctuated;
+use std::collections::HashMap;
+use syn::{
+    parse2, parse_quote, Block, Expr, File, Item, ItemFn, ItemMod, Local, LocalInit, Pat, PatIdent,
+    PatType, Stmt, WhereClause,
+};
+use tracing::trace;
 
 pub struct BlockParser;
 
 impl BlockParser {
-    pub fn dispatch(line: &str, lexer: &Lexer) -> Result<BenchmarkLine> {
-        let trimmed_line = line.trim_start();
-        println!("trimmed_line: {:?}", trimmed_line);
-
-        match trimmed_line {
-            _ if trimmed_line.starts_with("benchmarks") => match Self::benchmark(line) {
-                Ok((_remaining, parsed)) => Ok(BenchmarkLine {
-                    head: Some(parsed.to_string()),
-                    kind: LineKind::Mod,
-                    content: None,
-                    param_content: None,
-                    fn_body: None,
+    pub fn benchmark(input: &str) -> IResult<&str, &'static str> {
+        preceded(
+            multispace0, // Optional whitespace
+            alt((
+                map(tag("benchmarks!"), |_| "benchmarks"),
+                map(tag("benchmarks_instance_pallet!"), |_| {
+                    "benchmarks_instance_pallet"
+                }),
+                // The name used before `benchmarks_instance_pallet!`
+                map(tag("benchmarks_instance!"), |_| {
+                    "benchmarks_instance_pallet"
                 }),
-                Err(e) => Err(anyhow!("Error parsing benchmark: {:?}", e)),
-            },
-            _ if trimmed_line.starts_with("let") => match ParamParser::dispatch(line) {
-                Ok(parameter) => Ok(parameter),
-                Err(e) => Err(anyhow!("Error parsing parameter: {:?}", e)),
-            },
-            _ if Self::is_function_declaration(trimmed_line) => match Self::function(line) {
-                Ok((_remaining, parsed)) => {
-                    println!("matches function: {:?}", parsed);
-                    let (_remaining, fn_body) = Self::fn_body(parsed, lexer.0.as_str()).unwrap();
-                    Ok(BenchmarkLine {
-                        head: Some(parsed.to_string()),
-                        kind: LineKind::Fn,
-                        content: None,
-                        param_content: None,
-                        fn_body: Some(fn_body.to_string()),
-                    })
+            )),
+        )(input)
+    }
+
+    /// Finds the first `<macro>! { ... }` invocation of a v1 benchmarks macro in `tokens`,
+    /// looking into nested groups as well. Paths such as `frame_benchmarking::v1::benchmarks!`
+    /// are matched by their final segment.
+    pub fn find_invocation(tokens: TokenStream, config: &Config) -> Option<Invocation> {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (i, token) in tokens.iter().enumerate() {
+            match (token, tokens.get(i + 1), tokens.get(i + 2)) {
+                (
+                    TokenTree::Ident(ident),
+                    Some(TokenTree::Punct(bang)),
+                    Some(TokenTree::Group(body)),
+                ) if bang.as_char() == '!' && body.delimiter() == Delimiter::Brace => {
+                    if let Some(kind) = Self::macro_kind(&ident.to_string(), config) {
+                        return Some(Invocation {
+                            kind,
+                            start: tokens[Self::path_start(&tokens, i)].span().start(),
+                            body: body.clone(),
+                        });
+                    }
                 }
-                Err(e) => Err(anyhow!("Error parsing function: {:?}", e)),
-            },
-            _ if trimmed_line.starts_with("verify") => {
-                match Self::function(line) {
-                    Ok((_remaining, parsed)) => {
-                        let (_remaining, fn_body) = Self::fn_body(parsed, lexer.0.as_str()).unwrap();
-
-                        println!("matches verify");
-                        Ok(BenchmarkLine {
-                            head: Some(parsed.to_string()),
-                            kind: LineKind::Verify,
-                            content: None,
-                            param_content: None,
-                            fn_body: Some(fn_body.to_string()),
-                        })
+                (TokenTree::Group(group), _, _) => {
+                    if let Some(invocation) = Self::find_invocation(group.stream(), config) {
+                        return Some(invocation);
                     }
-                    Err(e) => Err(anyhow!("Error parsing function: {:?}", e)),
                 }
-            }
-            _ if trimmed_line.starts_with("}:") => Ok(BenchmarkLine {
-                head: None,
-                kind: LineKind::Extrinsic,
-                content: Some(line.to_string()),
-                param_content: None,
-                fn_body: None,
-            }),
-            _ if trimmed_line.starts_with("(")
-                || trimmed_line.starts_with("T::")
-                || trimmed_line.starts_with("}") =>
-            {
-               println!("matches other chars");
-                Ok(BenchmarkLine {
-                    head: None,
-                    kind: LineKind::Content,
-                    content: Some(line.to_string()),
-                    param_content: None,
-                    fn_body: None,
-                })
-            }
-            _ if trimmed_line.starts_with("for i") =>  {
-                println!("matches for line: {:?}", trimmed_line);
-                Ok(BenchmarkLine {
-                head: None,
-                kind: LineKind::Content,
-                content: Some(line.to_string()),
-                param_content: None,
-                fn_body: None,
-            }) },
-            _ => {
-                println!("matches other line: {:?}", trimmed_line);
-                Ok(BenchmarkLine {
-                    head: None,
-                    kind: LineKind::Content,
-                    content: Some(line.to_string()),
-                    param_content: None,
-                    fn_body: None,
-                })
+                _ => {}
             }
         }
+        None
     }
 
-    fn is_function_declaration(line: &str) -> bool {
-        let trimmed_line = line.trim();
-        if trimmed_line.contains("for") {
-            return false;
+    /// The v1 macro called `name`. Macros listed in `extra_macros` are treated like
+    /// `benchmarks!`, those in `extra_instance_macros` like `benchmarks_instance_pallet!`.
+    pub fn macro_kind(name: &str, config: &Config) -> Option<MacroKind> {
+        if let Ok(("", parsed)) = Self::benchmark(&format!("{}!", name)) {
+            return MacroKind::from_name(parsed);
         }
-        let without_whitespace = trimmed_line.split_whitespace().collect::<String>();
-        without_whitespace.ends_with("{")
+        Self::extra_macros(config).find_map(|(extra, kind)| (extra == name).then_some(kind))
     }
 
-    pub fn benchmark(input: &str) -> IResult<&str, &str> {
-        preceded(
-            multispace0, // Optional whitespace
-            alt((
-                map(tag("benchmarks!"), |_| "benchmarks"),
-                map(tag("benchmarks_instance_pallet!"), |_| {
-                    "benchmarks_instance_pallet"
-                }),
-            )),
-        )(input)
+    /// The names of the macros configured in addition to the built-in ones.
+    pub fn extra_macros(config: &Config) -> impl Iterator<Item = (&str, MacroKind)> {
+        let extra = config
+            .extra_macros
+            .iter()
+            .map(|path| (Self::last_segment(path), MacroKind::Benchmarks));
+        let instance = config
+            .extra_instance_macros
+            .iter()
+            .map(|path| (Self::last_segment(path), MacroKind::InstancePallet));
+        extra.chain(instance)
     }
 
-    pub fn function(input: &str) -> IResult<&str, &str> {
-        terminated(
-            preceded(multispace0, recognize(separated_list1(tag("_"), alpha1))),
-            preceded(multispace0, char('{')),
-        )(input)
+    // Strips a leading path like `crate::macros::` and a trailing `!` off `path`.
+    fn last_segment(path: &str) -> &str {
+        let path = path.trim().trim_end_matches('!').trim_end();
+        path.rsplit("::").next().unwrap_or(path).trim()
     }
 
-    pub fn fn_body<'a>(fn_name: &'a str, input: &'a str) -> IResult<&'a str, &'a str> {
-        //println!("fn_name: {:?}", fn_name);
-        //println!("input fn_body: {:?}", input);
-        // Find the function name in the input and move past it
-        let (input, _) = take_until(fn_name)(input)?;
-        let (input, _) = tag(fn_name)(input)?;
+    // Walks back from the macro name at `tokens[i]` over a path like `frame_benchmarking::`.
+    fn path_start(tokens: &[TokenTree], mut i: usize) -> usize {
+        while let [.., TokenTree::Punct(first), TokenTree::Punct(second)] = &tokens[..i] {
+            if first.as_char() != ':' || second.as_char() != ':' {
+                break;
+            }
+            i -= 2;
+            match i.checked_sub(1).map(|prev| &tokens[prev]) {
+                Some(TokenTree::Ident(_)) => i -= 1,
+                _ => break,
+            }
+        }
+        i
+    }
+}
 
-        // Skip whitespace and find the opening brace of the function body
-        let (input, _) = multispace0(input)?;
-        let (input, _) = char('{')(input)?;
+/// A v1 benchmarks macro invocation found by [`BlockParser::find_invocation`].
+pub struct Invocation {
+    pub kind: MacroKind,
+    /// Start of the macro path, e.g. of `frame_benchmarking` in `frame_benchmarking::benchmarks!`.
+    pub start: LineColumn,
+    /// The braced body holding the benchmarks.
+    pub body: Group,
+}
 
-        // Now capture everything inside the top-level curly braces
-        let (input, content) = take_until("}")(input)?;
+/// The v1 macro flavour, which decides the v2 module attribute and generics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroKind {
+    Benchmarks,
+    /// `benchmarks_instance_pallet!`, for pallets generic over an instance `I`.
+    InstancePallet,
+}
 
-        Ok((input, content))
+impl MacroKind {
+    /// The name of the built-in macro, as passed to [`MacroKind::from_name`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Benchmarks => "benchmarks",
+            Self::InstancePallet => "benchmarks_instance_pallet",
+        }
     }
 
-    pub fn ensure(input: &str) -> IResult<&str, &str> {
-        // Ignore leading whitespace, match "ensure!", and capture everything up to the ending ");"
-        let (input, _) = preceded(multispace0, tag("ensure!"))(input)?;
-        let (input, content) = delimited(
-            char('('),
-            // Capture everything inside the parentheses
-            take_until(");"),
-            // Expect the closing ");"
-            tag(");"),
-        )(input)?;
-
-        Ok((input, content))
+    /// The kind of an invocation like `benchmarks` or `benchmarks_instance_pallet!`.
+    pub fn from_name(input: &str) -> Option<Self> {
+        let name: String = input
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        match name.as_str() {
+            "benchmarks" => Some(Self::Benchmarks),
+            "benchmarks_instance_pallet" => Some(Self::InstancePallet),
+            _ => None,
+        }
     }
 }
 
 pub struct BlockWriter;
 
 impl BlockWriter {
-    pub fn dispatch_mod(input: &str) -> String {
-        // Check for benchmark-related keywords
-        if input.trim_start().starts_with("benchmarks!") {
-            Self::mod_item()
-        } else if input
-            .trim_start()
-            .starts_with("benchmarks_instance_pallet!")
+    pub fn dispatch_mod(kind: MacroKind, config: &Config) -> String {
+        match kind {
+            MacroKind::Benchmarks => Self::mod_item(config),
+            MacroKind::InstancePallet => Self::mod_instance_item(config),
+        }
+    }
+
+    pub fn mod_item(config: &Config) -> String {
+        format!("#[benchmarks]\nmod {} {{\n\n}}", config.module_name)
+    }
+
+    pub fn mod_instance_item(config: &Config) -> String {
+        format!(
+            "#[instance_benchmarks]\nmod {} {{\n\n}}",
+            config.module_name
+        )
+    }
+
+    /// Makes the code of an instanced pallet generic over `I`: `Pallet::<T>` becomes
+    /// `Pallet::<T, I>` and `<T as Config>` becomes `<T as Config<I>>`. The rest of `code`
+    /// is kept as written.
+    pub fn instance_generics(code: &str) -> Result<String> {
+        let tokens: TokenStream = code
+            .parse()
+            .map_err(|e| anyhow!("Error tokenizing generated code: {}", e))?;
+        let mut insertions = Vec::new();
+        Self::instance_insertions(tokens, &mut insertions);
+        let mut code = code.to_string();
+        let offsets: Vec<(usize, &str)> = insertions
+            .into_iter()
+            .map(|(position, text)| (Lexer::byte_offset(&code, position), text))
+            .collect();
+        for (offset, text) in offsets.into_iter().rev() {
+            code.insert_str(offset, text);
+        }
+        Ok(code)
+    }
+
+    // Collects, in source order, the positions where `instance_generics` adds `I`.
+    fn instance_insertions(tokens: TokenStream, insertions: &mut Vec<(LineColumn, &'static str)>) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => Self::instance_insertions(group.stream(), insertions),
+                TokenTree::Ident(ident)
+                    if ident == "T" && Self::is_pallet_generic(&tokens, index) =>
+                {
+                    insertions.push((ident.span().end(), ", I"));
+                }
+                TokenTree::Ident(ident)
+                    if ident == "Config" && Self::is_pallet_config(&tokens, index) =>
+                {
+                    insertions.push((ident.span().end(), "<I>"));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // `T` is the only argument of `Pallet<T>` or `Pallet::<T>`.
+    fn is_pallet_generic(tokens: &[TokenTree], index: usize) -> bool {
+        let is_pallet = |at: usize| matches!(tokens.get(at), Some(TokenTree::Ident(ident)) if ident == "Pallet");
+        Self::is_punct(tokens.get(index + 1), '>')
+            && index >= 2
+            && Self::is_punct(tokens.get(index - 1), '<')
+            && (is_pallet(index - 2)
+                || (index >= 4
+                    && Self::is_punct(tokens.get(index - 2), ':')
+                    && Self::is_punct(tokens.get(index - 3), ':')
+                    && is_pallet(index - 4)))
+    }
+
+    // `Config` is the pallet trait in `<T as Config>` or `<T as crate::Config>`, and with
+    // `super::` or `self::`; any other path names the trait of another pallet.
+    fn is_pallet_config(tokens: &[TokenTree], index: usize) -> bool {
+        if !Self::is_punct(tokens.get(index + 1), '>') {
+            return false;
+        }
+        let mut start = index;
+        if start >= 3
+            && Self::is_punct(tokens.get(start - 1), ':')
+            && Self::is_punct(tokens.get(start - 2), ':')
         {
-            Self::mod_instance_item()
-        } else {
-            "Error: Invalid benchmark module type".to_string()
+            match &tokens[start - 3] {
+                TokenTree::Ident(segment)
+                    if segment == "crate" || segment == "super" || segment == "self" =>
+                {
+                    start -= 3
+                }
+                _ => return false,
+            }
         }
+        start >= 1 && matches!(&tokens[start - 1], TokenTree::Ident(ident) if ident == "as")
     }
 
-    pub fn mod_item() -> String {
-        format!("#[benchmarks]\nmod benchmarks{{\n\n}}")
+    fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+        matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
     }
 
-    pub fn mod_instance_item() -> String {
-        format!("#[instance_benchmarks]\nmod benchmarks{{\n\n}}")
+    /// Moves the predicates of a v1 `where_clause` block into the attribute of the printed
+    /// module, as in `#[benchmarks(where T: Config)]`.
+    pub fn where_clause_into_mod(code: &str, predicates: &str) -> Result<String> {
+        let where_clause: WhereClause = syn::parse_str(&format!("where {}", predicates))
+            .map_err(|e| anyhow!("Failed to parse where clause: {}", e))?;
+        // Attribute arguments are printed as raw tokens, so format the predicates on a fn
+        let file: File = parse_quote!(fn f() #where_clause {});
+        let printed = prettyplease::unparse(&file);
+        let printed = printed.split_whitespace().collect::<Vec<_>>().join(" ");
+        let predicates = printed
+            .trim_start_matches("fn f() where ")
+            .trim_end_matches(" {}")
+            .trim_end_matches(',');
+        for attr in ["benchmarks", "instance_benchmarks"] {
+            if let Some(rest) = code.strip_prefix(&format!("#[{}]", attr)) {
+                return Ok(format!("#[{}(where {})]{}", attr, predicates, rest));
+            }
+        }
+        Err(anyhow!("Generated module has no benchmarks attribute"))
     }
 
-    pub fn fn_item(function_name: &str) -> String {
-        format!(
-            "#[benchmark]\nfn {}() -> Result<(), BenchmarkError> {{\n\n}}",
-            function_name
-        )
+    pub fn fn_item(function_name: &str, attributes: &[String], config: &Config) -> String {
+        let output = if config.returns_result() {
+            " -> Result<(), BenchmarkError>"
+        } else {
+            ""
+        };
+        let attribute = if attributes.is_empty() {
+            "#[benchmark]".to_string()
+        } else {
+            format!("#[benchmark({})]", attributes.join(", "))
+        };
+        format!("{}\nfn {}(){} {{\n\n}}", attribute, function_name, output)
     }
 
     pub fn fn_into_mod(ast: Vec<Item>) -> Result<ItemMod> {
@@ -241,14 +326,19 @@ impl BlockWriter {
         // Flag to indicate if the function body has been inserted
         let mut inserted = false;
 
-        // Iterate over the items in the module
-        for item in &mut mod_block.content.as_mut().unwrap().1 {
+        // Iterate in reverse so the body goes into the function that was added last
+        let items = &mut mod_block
+            .content
+            .as_mut()
+            .ok_or_else(|| anyhow!("Module has no content"))?
+            .1;
+        for item in items.iter_mut().rev() {
             // Match only on functions
             if let Item::Fn(ItemFn { ref mut block, .. }) = item {
                 // Replace the existing block with the new one
-                *block = Box::new(body.clone());
+                **block = body.clone();
                 inserted = true;
-                break; // Assuming you only want to insert into the first found function
+                break;
             }
         }
 
@@ -259,61 +349,243 @@ impl BlockWriter {
 
         // Convert the modified module back into a string
         let result = quote!(#mod_block).to_string();
-        println!("result: {:?}", result);
+        trace!(%result, "inserted benchmark body");
 
         Ok(result)
     }
 
-    pub fn extrinsic_into_fn(ast: Vec<Item>, ext: &str) -> Result<String> {
+    pub fn extrinsic_into_fn(
+        ast: Vec<Item>,
+        ext: &str,
+        verify: Option<&str>,
+        config: &Config,
+    ) -> Result<String> {
         let mut modified_ast = ast.clone();
-        let mut last_mod_function: Option<&mut ItemFn> = None;
-        // Iterate in reverse to find the last mod block
-        for item in modified_ast.iter_mut().rev() {
-            if let Item::Mod(ItemMod { content: Some((_, items)), .. }) = item {
-                // Iterate in reverse to find the last function within this mod block
-                for item in items.iter_mut().rev() {
-                    if let Item::Fn(function) = item {
-                        last_mod_function = Some(function);
-                        break;
-                    }
-                }
-                if last_mod_function.is_some() {
-                    break;
-                }
-            }
-        }
 
-        if let Some(function) = last_mod_function {
+        if let Some(function) = Self::last_mod_function(&mut modified_ast) {
             // Parse the extrinsic string into a TokenStream
-            let insert_tokens: TokenStream = ext.parse().expect("Failed to parse into TokenStream");
-            let extrinsic = parse2::<ExtrinsicCall>(insert_tokens)?;
+            let insert_tokens: TokenStream = ext
+                .parse()
+                .map_err(|e| anyhow!("Failed to parse into TokenStream: {:?}", e))?;
+            let tokens: Vec<TokenTree> = insert_tokens.clone().into_iter().collect();
+            match tokens.as_slice() {
+                // `}: { ... }` measures a block instead of a dispatchable
+                [TokenTree::Group(group)] if group.delimiter() == Delimiter::Brace => {
+                    let statements = Self::measured_block(&group.stream().to_string(), verify)?;
+                    function.block.stmts.extend(statements);
+                }
+                _ => {
+                    let extrinsic = parse2::<ExtrinsicCall>(insert_tokens)?;
 
-            // Convert the parsed ExtrinsicCall into a Stmt
-            let stmt = Stmt::Expr(Expr::Verbatim(quote! { #extrinsic }));
-            function.block.stmts.push(stmt);
-       } else {
+                    // Convert the parsed ExtrinsicCall into a Stmt
+                    let stmt: Stmt = parse2(quote! { #extrinsic; })?;
+                    function.block.stmts.push(stmt);
+                }
+            }
+        } else {
             return Err(anyhow!("No function found in AST"));
         }
 
-        let cleaned_ast = Self::remove_duplicate_mods(modified_ast);
+        let cleaned_ast = Self::remove_duplicate_mods(modified_ast, config);
 
         // Convert the modified AST back to a string
         let result = quote! {
-        #( #cleaned_ast )*
-        }.to_string();
+            #( #cleaned_ast )*
+        }
+        .to_string();
 
-        println!("result extrinsic: {:?}", result);
+        trace!(%result, "inserted extrinsic call");
 
         Ok(result)
     }
 
-    pub fn remove_duplicate_mods(ast: Vec<Item>) -> Vec<Item> {
+    // Turns the body of a v1 block call into a `#[block]` statement. In v1 its `let`
+    // bindings are in scope for `verify`, so the ones it uses are declared in front of the
+    // block and only assigned inside it.
+    fn measured_block(code: &str, verify: Option<&str>) -> Result<Vec<Stmt>> {
+        let mut block = Self::clean_code_block(code, &[])?;
+        let mut verify_tokens = TokenStream::new();
+        if let Some(verify) = verify {
+            verify_tokens = verify
+                .parse()
+                .map_err(|e| anyhow!("Error tokenizing verify block: {}", e))?;
+        }
+        let used = ParamWriter::free_identifiers(verify_tokens.clone());
+        let mut bound = HashMap::new();
+        for stmt in &block.stmts {
+            if let Some(ident) = Self::hoistable_binding(stmt) {
+                *bound.entry(ident.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        let mut statements = Vec::new();
+        for index in 0..block.stmts.len() {
+            let Some(ident) = Self::hoistable_binding(&block.stmts[index]).cloned() else {
+                continue;
+            };
+            let name = ident.to_string();
+            if !used.contains(&name) {
+                continue;
+            }
+            // The earlier bindings would shadow the declaration in front of the block
+            if bound[&name] > 1 {
+                return Err(anyhow!(
+                    "`{}` is bound more than once in the measured block and used by verify",
+                    name
+                ));
+            }
+            let rest = &block.stmts[index + 1..];
+            let mutated = Self::is_mutated(quote!(#(#rest)* #verify_tokens), &name);
+            let Stmt::Local(local) = &mut block.stmts[index] else {
+                continue;
+            };
+            let Some(init) = local.init.take() else {
+                continue;
+            };
+            let mut declaration = local.clone();
+            // Assigning the declared variable once does not need `mut`
+            if !mutated {
+                let pat = match &mut declaration.pat {
+                    Pat::Type(PatType { pat, .. }) => pat.as_mut(),
+                    pat => pat,
+                };
+                if let Pat::Ident(binding) = pat {
+                    binding.mutability = None;
+                }
+            }
+            statements.push(Stmt::Local(declaration));
+            let expr = init.expr;
+            block.stmts[index] = parse_quote!(#ident = #expr;);
+        }
+        statements.push(parse_quote!(#[block] #block));
+        Ok(statements)
+    }
+
+    // Whether `tokens` may change the variable `name`: assign or index into it, borrow it
+    // mutably or access a method or field of it, since a method could take `&mut self`.
+    fn is_mutated(tokens: TokenStream, name: &str) -> bool {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        tokens.iter().enumerate().any(|(i, token)| match token {
+            TokenTree::Group(group) => Self::is_mutated(group.stream(), name),
+            TokenTree::Ident(ident) if ident == name => {
+                let borrowed = i >= 2
+                    && matches!(
+                        (&tokens[i - 2], &tokens[i - 1]),
+                        (TokenTree::Punct(and), TokenTree::Ident(mutability))
+                            if and.as_char() == '&' && mutability == "mut"
+                    );
+                let indexed = matches!(
+                    tokens.get(i + 1),
+                    Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Bracket
+                );
+                borrowed || indexed || Self::is_assignment(&tokens[i + 1..])
+            }
+            _ => false,
+        })
+    }
+
+    // Whether `tokens` start with `=`, `.` or a compound assignment like `+=` or `<<=`.
+    fn is_assignment(tokens: &[TokenTree]) -> bool {
+        let mut operator = String::new();
+        for token in tokens {
+            let TokenTree::Punct(punct) = token else {
+                break;
+            };
+            operator.push(punct.as_char());
+            if punct.spacing() == Spacing::Alone {
+                break;
+            }
+        }
+        (operator.starts_with('=') && !operator.starts_with("=="))
+            || operator.starts_with('.')
+            || ["+=", "-=", "*=", "/=", "%=", "^=", "&=", "|=", "<<=", ">>="]
+                .iter()
+                .any(|compound| operator.starts_with(compound))
+    }
+
+    // The variable a `let name = ...;` or `let mut name: Type = ...;` statement binds.
+    fn hoistable_binding(stmt: &Stmt) -> Option<&Ident> {
+        let Stmt::Local(Local {
+            pat,
+            init: Some(LocalInit { diverge: None, .. }),
+            ..
+        }) = stmt
+        else {
+            return None;
+        };
+        let pat = match pat {
+            Pat::Type(PatType { pat, .. }) => pat,
+            pat => pat,
+        };
+        match pat {
+            Pat::Ident(PatIdent {
+                ident,
+                by_ref: None,
+                subpat: None,
+                ..
+            }) => Some(ident),
+            _ => None,
+        }
+    }
+
+    pub fn verify_into_fn(
+        ast: Vec<Item>,
+        verify: Option<Block>,
+        config: &Config,
+    ) -> Result<String> {
+        let mut modified_ast = ast;
+
+        let function = Self::last_mod_function(&mut modified_ast)
+            .ok_or_else(|| anyhow!("No function found in AST"))?;
+        if let Some(verify) = verify {
+            function.block.stmts.extend(verify.stmts);
+        }
+        if config.returns_result() {
+            function
+                .block
+                .stmts
+                .push(Stmt::Expr(parse_quote!(Ok(())), None));
+        }
+
+        Ok(quote! {
+            #( #modified_ast )*
+        }
+        .to_string())
+    }
+
+    // Finds the last function within the last mod block of the AST.
+    fn last_mod_function(ast: &mut [Item]) -> Option<&mut ItemFn> {
+        // Iterate in reverse to find the last mod block
+        for item in ast.iter_mut().rev() {
+            if let Item::Mod(ItemMod {
+                content: Some((_, items)),
+                ..
+            }) = item
+            {
+                // Iterate in reverse to find the last function within this mod block
+                if let Some(function) = items.iter_mut().rev().find_map(|item| match item {
+                    Item::Fn(function) => Some(function),
+                    _ => None,
+                }) {
+                    return Some(function);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn remove_duplicate_mods(ast: Vec<Item>, config: &Config) -> Vec<Item> {
         let mut new_ast = Vec::new();
         let mut last_non_empty_benchmarks_mod: Option<Item> = None;
 
         for item in ast {
-            if let Item::Mod(ItemMod { ident, content: Some((_, items)), .. }) = &item {
-                if ident == "benchmarks" {
+            if let Item::Mod(ItemMod {
+                ident,
+                content: Some((_, items)),
+                ..
+            }) = &item
+            {
+                if *ident == config.module_name {
                     // Check if the mod contains any function with a body
                     let contains_non_empty_fn = items.iter().any(|item| {
                         matches!(item, Item::Fn(ItemFn { block, .. }) if !block.stmts.is_empty())
@@ -338,69 +610,111 @@ impl BlockWriter {
         new_ast
     }
 
+    /// Parses a v1 benchmark body into a block. Components become function parameters, so
+    /// only their setup after `=>` is kept, in place of the declaration. Like v1, the `let`
+    /// statements in front of the first component run before any setup. Setup is taken
+    /// from the resolved `params` when a component is listed there, and common components
+    /// the body only uses run their setup right after those `let` statements.
+    pub fn clean_code_block(code_block: &str, params: &[BenchmarkParameter]) -> Result<Block> {
+        let tokens: TokenStream = code_block
+            .parse()
+            .map_err(|e| anyhow!("Error tokenizing code block: {}", e))?;
+        let parts = ParamParser::split_body(tokens);
+        let mut declared = Vec::new();
+        for part in &parts {
+            if let BodyPart::Component(component) = part {
+                let text = TokenStream::from_iter(component.iter().cloned()).to_string();
+                let (_, param) = ParamParser::let_declaration(&text)
+                    .map_err(|e| anyhow!("Invalid component `{}`: {:?}", text, e))?;
+                let resolved = params.iter().find(|resolved| resolved.name == param.name);
+                declared.push(resolved.cloned().unwrap_or(param));
+            }
+        }
 
-    pub fn clean_code_block(code_block: &str) -> Result<Block> {
-        let cleaned_code = code_block
-            .split(';')
-            .map(|line| {
-                let trimmed_line = line.trim();
-                if let Some(start_idx) = trimmed_line.find("=>") {
-                    trimmed_line[start_idx + 2..].trim()
-                } else {
-                    trimmed_line
+        let mut statements = TokenStream::new();
+        let mut rest = TokenStream::new();
+        let mut components = declared.iter();
+        for (index, part) in parts.into_iter().enumerate() {
+            match part {
+                BodyPart::Code(code) if index == 0 => {
+                    let (lets, code) = Self::leading_lets(code);
+                    statements.extend(lets);
+                    rest.extend(code);
                 }
-            })
-            .filter(|line| !line.is_empty())
-            .map(|line| {
-                if line.ends_with(';') {
-                    line.to_string()
-                } else {
-                    format!("{};", line)
+                BodyPart::Code(code) => rest.extend(code),
+                BodyPart::Component(_) => {
+                    if let Some(param) = components.next() {
+                        Self::push_setup(&mut rest, param)?;
+                    }
                 }
-            }) // Ensure each line ends with a semicolon
-            .collect::<Vec<String>>()
-            .join(" ");
-
-        // Wrap the cleaned code in braces to form a valid block
-        let block_str = format!("{{ {} }}", cleaned_code);
-        println!("block_str: {:?}", block_str);
+            }
+        }
+        for param in params {
+            if !declared.iter().any(|declared| declared.name == param.name) {
+                Self::push_setup(&mut statements, param)?;
+            }
+        }
+        statements.extend(rest);
+        let block = quote!({ #statements });
+        trace!(%block, "cleaned code block");
 
-        // Parse the cleaned code into a syn::Block
-        syn::parse_str::<Block>(&block_str)
-            .map_err(|e| anyhow!("Error parsing cleaned code into a Block: {}", e))
+        let mut block = parse2::<Block>(block)
+            .map_err(|e| anyhow!("Error parsing cleaned code into a Block: {}", e))?;
+        // v1 discards the value of a trailing expression, v2 code may follow it
+        if let Some(Stmt::Expr(expr, semi @ None)) = block.stmts.last_mut() {
+            let block_like = matches!(
+                expr,
+                Expr::Block(_)
+                    | Expr::ForLoop(_)
+                    | Expr::If(_)
+                    | Expr::Loop(_)
+                    | Expr::Match(_)
+                    | Expr::Unsafe(_)
+                    | Expr::While(_)
+            );
+            if !block_like {
+                *semi = Some(Default::default());
+            }
+        }
+        Ok(block)
     }
 
-    pub fn extrinsic(input: &str) -> String {
-        let re = regex::Regex::new(r"(\w*):\s*_<T::(\w+)>\((\w+), (\w+)\)").unwrap();
-        let replacement = "#[extrinsic_call]\n_<T::${2}>(${3}, ${4})";
-        let output = re.replace(input, replacement).to_string();
-
-        // Regex to match '}' with any preceding whitespace (including tabs and newlines)
-        let trim_re = regex::Regex::new(r"[\s\t]*\}\s*").unwrap();
-        trim_re.replace_all(&output, "").to_string()
+    // Splits the `let` statements at the start of `code` off the statements after them.
+    fn leading_lets(code: TokenStream) -> (TokenStream, TokenStream) {
+        let mut tokens: Vec<TokenTree> = code.into_iter().collect();
+        let mut end = 0;
+        while matches!(tokens.get(end), Some(TokenTree::Ident(ident)) if ident == "let") {
+            match tokens[end..].iter().position(ParamParser::is_semicolon) {
+                Some(semi) => end += semi + 1,
+                None => break,
+            }
+        }
+        let rest = tokens.split_off(end);
+        (tokens.into_iter().collect(), rest.into_iter().collect())
     }
 
+    // Appends the setup of a component as a statement, blocks as they are.
+    fn push_setup(statements: &mut TokenStream, param: &BenchmarkParameter) -> Result<()> {
+        let Some(setup) = &param.setup else {
+            return Ok(());
+        };
+        let setup: TokenStream = setup
+            .parse()
+            .map_err(|e| anyhow!("Error tokenizing component setup: {}", e))?;
+        let tokens: Vec<TokenTree> = setup.clone().into_iter().collect();
+        match tokens.as_slice() {
+            [TokenTree::Group(group)] if group.delimiter() == Delimiter::Brace => {
+                statements.extend(setup)
+            }
+            _ => statements.extend(quote!(#setup;)),
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[test]
-    fn test_dispatch_should_call_benchmarks() {
-        let lexer = Lexer::new("".to_string());
-        let input = "benchmarks!";
-        let line = BlockParser::dispatch(input, &lexer).unwrap();
-        assert_eq!(line.head, Some("benchmarks".to_string()));
-    }
-
-    #[test]
-    fn test_dispatch_should_call_function() {
-        let lexer = Lexer::new("".to_string());
-        let input = "propose_proposed {";
-        let line = BlockParser::dispatch(input, &lexer).unwrap();
-        assert_eq!(line.head, Some("propose_proposed".to_string()));
-    }
-
     #[test]
     fn test_benchmarks_instance_pallet() {
         let input = "benchmarks_instance_pallet!";
@@ -416,48 +730,336 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_valid_function_call() {
-        let input = "propose_proposed {";
-        let (_, parsed) = BlockParser::function(input).unwrap();
-        assert_eq!(parsed, "propose_proposed");
+    fn test_mod_item_generation() {
+        let input = "benchmarks!";
+        let (_, parsed) = BlockParser::benchmark(input).unwrap();
+        let expected = "#[benchmarks]\nmod benchmarks {\n\n}";
+        let actual =
+            BlockWriter::dispatch_mod(MacroKind::from_name(parsed).unwrap(), &Config::default());
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn test_parse_verify_function_call() {
-        let input = "verify {";
-        let (_, parsed) = BlockParser::function(input).unwrap();
-        assert_eq!(parsed, "verify");
+    fn test_mod_instance_item_generation() {
+        let input = "benchmarks_instance_pallet!";
+        let (_, parsed) = BlockParser::benchmark(input).unwrap();
+        let expected = "#[instance_benchmarks]\nmod benchmarks {\n\n}";
+        let actual =
+            BlockWriter::dispatch_mod(MacroKind::from_name(parsed).unwrap(), &Config::default());
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn test_parse_function_call_with_whitespace() {
-        let input = "   propose_proposed {"; // Leading whitespace
-        let (_, parsed) = BlockParser::function(input).unwrap();
-        assert_eq!(parsed, "propose_proposed");
+    fn test_instance_generics() {
+        let code = "Pallet::<T>::on_initialize(b.into()); let p: Pallet<T>; Pallet::<T, I>::get(); MyPallet::<T>::get(); <T as Config>::Currency::free(); <T as crate::Config>::X::get(); <T as frame_system::Config>::RuntimeOrigin::root(); <T as pallet_balances::Config>::Balance::zero(); <T as super::Config>::Y::get(); impl_benchmark_test_suite!(Pallet::<T>, new_test_ext(), Test);";
+        let expected = "Pallet::<T, I>::on_initialize(b.into()); let p: Pallet<T, I>; Pallet::<T, I>::get(); MyPallet::<T>::get(); <T as Config<I>>::Currency::free(); <T as crate::Config<I>>::X::get(); <T as frame_system::Config>::RuntimeOrigin::root(); <T as pallet_balances::Config>::Balance::zero(); <T as super::Config<I>>::Y::get(); impl_benchmark_test_suite!(Pallet::<T, I>, new_test_ext(), Test);";
+        assert_eq!(BlockWriter::instance_generics(code).unwrap(), expected);
     }
 
     #[test]
-    fn test_parse_function_call_with_new_name() {
-        let input = "propose_proposed_with_new_name {";
-        let (_, parsed) = BlockParser::function(input).unwrap();
-        assert_eq!(parsed, "propose_proposed_with_new_name");
+    fn test_fn_item_generation() {
+        let expected = "#[benchmark]\nfn propose_proposed() -> Result<(), BenchmarkError> {\n\n}";
+        let actual = BlockWriter::fn_item("propose_proposed", &[], &Config::default());
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn test_mod_item_generation() {
-        let input = "benchmarks!";
-        let (_, parsed) = BlockParser::benchmark(input).unwrap();
-        let expected = "#[instance_benchmarks]\nmod benchmarks {\n\n}";
-        let actual = BlockWriter::dispatch_mod(parsed);
-        assert_eq!(actual, expected);
+    fn test_items_follow_config() {
+        let config = Config {
+            module_name: "bench".to_string(),
+            frame_version: "0.9.40".parse().unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            BlockWriter::dispatch_mod(MacroKind::Benchmarks, &config),
+            "#[benchmarks]\nmod bench {\n\n}"
+        );
+        assert_eq!(
+            BlockWriter::fn_item("add_registrar", &[], &config),
+            "#[benchmark]\nfn add_registrar() {\n\n}"
+        );
+    }
+
+    fn invocation_kind(source: &str, config: &Config) -> Option<MacroKind> {
+        BlockParser::find_invocation(source.parse().unwrap(), config).map(|found| found.kind)
     }
 
     #[test]
-    fn test_fn_item_generation() {
-        let input = "propose_proposed {";
-        let (_, parsed) = BlockParser::function(input).unwrap();
-        let expected = "#[benchmark]\nfn propose_proposed() -> Result<(), BenchmarkError> {\n\n}";
-        let actual = BlockWriter::dispatch_mod(parsed);
-        assert_eq!(actual, expected);
+    fn test_invocation_by_final_path_segment() {
+        let config = Config {
+            extra_macros: vec!["crate::macros::pallet_benchmarks!".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            invocation_kind("frame_benchmarking::v1::benchmarks! {}", &config),
+            Some(MacroKind::Benchmarks)
+        );
+        assert_eq!(
+            invocation_kind("benchmarks_instance! {}", &config),
+            Some(MacroKind::InstancePallet)
+        );
+        assert_eq!(
+            invocation_kind("crate::benchmarking_macros::benchmarks! {}", &config),
+            Some(MacroKind::Benchmarks)
+        );
+        assert_eq!(
+            invocation_kind("pallet_benchmarks! {}", &config),
+            Some(MacroKind::Benchmarks)
+        );
+        assert_eq!(
+            invocation_kind("frame_benchmarking::whitelist! {}", &config),
+            None
+        );
+
+        let found = BlockParser::find_invocation(
+            "mod bench { use super::*; frame_benchmarking::benchmarks! { a {}: _() } }"
+                .parse()
+                .unwrap(),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(found.start.column, 26);
+        assert_eq!(found.body.stream().to_string(), "a { } : _ ()");
+    }
+
+    #[test]
+    fn test_invocation_with_extra_macro() {
+        let config = Config {
+            extra_macros: vec!["pallet_benchmarks".to_string()],
+            extra_instance_macros: vec!["crate::instance_benchmarks!".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            invocation_kind("benchmarks !{}", &config),
+            Some(MacroKind::Benchmarks)
+        );
+        assert_eq!(
+            invocation_kind("\tpallet_benchmarks ! {}", &config),
+            Some(MacroKind::Benchmarks)
+        );
+        assert_eq!(
+            invocation_kind("instance_benchmarks! {}", &config),
+            Some(MacroKind::InstancePallet)
+        );
+        assert_eq!(invocation_kind("pallet_benchmarks {}", &config), None);
+        assert_eq!(invocation_kind("pallet_benchmarks!(a)", &config), None);
+        assert_eq!(invocation_kind("other_benchmarks! {}", &config), None);
+    }
+
+    #[test]
+    fn test_where_clause_into_mod() {
+        let predicates = "T: pallet_balances::Config,\n\t\t\tT::Balance: From<u32>, // bound\n";
+        let code = BlockWriter::where_clause_into_mod(
+            "#[instance_benchmarks]\nmod benchmarks {}\n",
+            predicates,
+        )
+        .unwrap();
+        assert_eq!(
+            code,
+            "#[instance_benchmarks(where T: pallet_balances::Config, T::Balance: From<u32>)]\nmod benchmarks {}\n"
+        );
+        assert!(BlockWriter::where_clause_into_mod("mod benchmarks {}", "T: Config").is_err());
+        assert!(
+            BlockWriter::where_clause_into_mod("#[benchmarks]\nmod benchmarks {}", "T Config")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_clean_code_block_drops_component_declarations() {
+        let block = BlockWriter::clean_code_block(
+            "let x in 0 .. T::MaxAdditionalFields::get();\n let r in 1 .. 10 => add_registrars::<T>(r)?;",
+            &[],
+        )
+        .unwrap();
+        let expected = quote!({
+            add_registrars::<T>(r)?;
+        });
+        assert_eq!(quote!(#block).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_clean_code_block_keeps_setup_order() {
+        let block = BlockWriter::clean_code_block(
+            r#"let caller: T::AccountId = whitelisted_caller();
+		let r in 1 .. T::MaxRegistrars::get() => add_registrars::<T>(r)?;
+		let s in 0 .. 10 => ();
+		let x in 0 .. T::MaxAdditionalFields::get() => {
+			let info = create_identity_info::<T>(x);
+			Identity::<T>::set_identity(origin::<T>(&caller), Box::new(info))?;
+		};
+		let fee = 10u32.into();
+		ensure!(fee > 0u32.into(), "no fee");"#,
+            &[],
+        )
+        .unwrap();
+        let expected = quote!({
+            let caller: T::AccountId = whitelisted_caller();
+            add_registrars::<T>(r)?;
+            {
+                let info = create_identity_info::<T>(x);
+                Identity::<T>::set_identity(origin::<T>(&caller), Box::new(info))?;
+            }
+            let fee = 10u32.into();
+            ensure!(fee > 0u32.into(), "no fee");
+        });
+        assert_eq!(
+            quote!(#block).to_string().replace(' ', ""),
+            expected.to_string().replace(' ', "")
+        );
+
+        // Without `let` statements in front of it, the first setup runs first
+        let block = BlockWriter::clean_code_block(
+            r#"let r in 1 .. T::MaxRegistrars::get() => add_registrars::<T>(r)?;
+		let caller = {
+			request_judgement::<T>(r)?;
+			whitelisted_caller()
+		};"#,
+            &[],
+        )
+        .unwrap();
+        let expected = quote!({
+            add_registrars::<T>(r)?;
+            let caller = {
+                request_judgement::<T>(r)?;
+                whitelisted_caller()
+            };
+        });
+        assert_eq!(
+            quote!(#block).to_string().replace(' ', ""),
+            expected.to_string().replace(' ', "")
+        );
+    }
+
+    #[test]
+    fn test_measured_block_declares_bindings_used_by_verify() {
+        let statements = BlockWriter::measured_block(
+            r#"let mut weight: Weight = Pallet::<T>::on_initialize(n);
+		let unused = 1;
+		let mut total = 1;
+		total += 1;
+		let total = total + 1;
+		Pallet::<T>::on_finalize(n)"#,
+            Some("assert!(weight.ref_time() > 0);"),
+        )
+        .unwrap();
+        let expected = quote!(
+            let mut weight: Weight;
+            #[block]
+            {
+                weight = Pallet::<T>::on_initialize(n);
+                let unused = 1;
+                let mut total = 1;
+                total += 1;
+                let total = total + 1;
+                Pallet::<T>::on_finalize(n);
+            }
+        );
+        assert_eq!(
+            quote!(#(#statements)*).to_string().replace(' ', ""),
+            expected.to_string().replace(' ', "")
+        );
+    }
+
+    #[test]
+    fn test_measured_block_drops_mut_of_variable_assigned_once() {
+        let statements = BlockWriter::measured_block(
+            "let mut who: T::AccountId = whitelisted_caller(); let mut count = 0; count += 1;",
+            Some("assert_eq!(who, caller); assert_eq!(count, 1);"),
+        )
+        .unwrap();
+        let expected = quote!(
+            let who: T::AccountId;
+            let mut count;
+            #[block]
+            {
+                who = whitelisted_caller();
+                count = 0;
+                count += 1;
+            }
+        );
+        assert_eq!(
+            quote!(#(#statements)*).to_string().replace(' ', ""),
+            expected.to_string().replace(' ', "")
+        );
+    }
+
+    #[test]
+    fn test_measured_block_rejects_shadowed_binding_used_by_verify() {
+        let Err(error) = BlockWriter::measured_block(
+            "let total = 1; let total = total + 1;",
+            Some("assert_eq!(total, 2);"),
+        ) else {
+            panic!("shadowed binding was hoisted");
+        };
+        assert_eq!(
+            error.to_string(),
+            "`total` is bound more than once in the measured block and used by verify"
+        );
+        assert!(BlockWriter::measured_block(
+            "let total = 1; let total = total + 1;",
+            Some("assert!(true);")
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_clean_code_block_terminates_trailing_expression() {
+        let block = BlockWriter::clean_code_block(
+            "assert_last_event::<T>(Event::<T>::IdentitySet { who: caller }.into())",
+            &[],
+        )
+        .unwrap();
+        assert!(matches!(block.stmts[..], [Stmt::Expr(_, Some(_))]));
+    }
+
+    #[test]
+    fn test_clean_code_block_follows_rust_statements() {
+        let block = BlockWriter::clean_code_block(
+            r#"let n in 1 .. 100;
+		let origin =
+			T::RegistrarOrigin::try_successful_origin()
+				.map_err(|_| BenchmarkError::Weightless)?;
+		ensure!(
+			Registrars::<T>::get().len() as u32 == n,
+			"Registrars not set up correctly.",
+		);
+		let info = IdentityInfo::default()
+			.with_display(b"name".to_vec())
+			.with_fields(n);
+		let judgement = match info.fields() {
+			0 => Judgement::Unknown,
+			_ => Judgement::Reasonable,
+		};
+		for i in 0 .. n {
+			add_sub::<T>(i)?;
+		}"#,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(block.stmts.len(), 5);
+        let expected = quote!({
+            let origin = T::RegistrarOrigin::try_successful_origin()
+                .map_err(|_| BenchmarkError::Weightless)?;
+            ensure!(
+                Registrars::<T>::get().len() as u32 == n,
+                "Registrars not set up correctly.",
+            );
+            let info = IdentityInfo::default()
+                .with_display(b"name".to_vec())
+                .with_fields(n);
+            let judgement = match info.fields() {
+                0 => Judgement::Unknown,
+                _ => Judgement::Reasonable,
+            };
+            for i in 0..n {
+                add_sub::<T>(i)?;
+            }
+        });
+        // Parsed and quoted tokens only differ in spacing
+        assert_eq!(
+            quote!(#block).to_string().replace(' ', ""),
+            expected.to_string().replace(' ', "")
+        );
     }
 }
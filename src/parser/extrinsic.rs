@@ -1,33 +1,46 @@
-use quote::ToTokens;
-use syn::parse::{Parse, ParseBuffer, ParseStream, Result};
-use syn::{Attribute, Expr, Type, Generics, Path, PathArguments, PathSegment, Ident, Token, punctuated::Punctuated, TypePath};
-use proc_macro2::{TokenStream};
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream, Result};
+use syn::{punctuated::Punctuated, Attribute, Expr, Token, Type};
 
+/// The call measured by a v1 benchmark: `_(...)`, `_<Origin>(...)` or `name(...)`, with an
+/// optional origin type cast onto the first argument.
 pub struct ExtrinsicCall {
     attribute: Vec<Attribute>,
-    underscore: Token![_],
-    runtime_origin: Type,
+    name: CallName,
+    runtime_origin: Option<Type>,
     args: Punctuated<Expr, Token![,]>,
 }
 
+enum CallName {
+    Underscore(Token![_]),
+    Named(Ident),
+}
+
 impl Parse for ExtrinsicCall {
     fn parse(input: ParseStream) -> Result<Self> {
         let attribute = Attribute::parse_outer(input)?;
 
-        let underscore: Token![_] = input.parse()?;
-        let runtime_origin: Type = {
+        let name = if input.peek(Token![_]) {
+            CallName::Underscore(input.parse()?)
+        } else {
+            CallName::Named(input.parse()?)
+        };
+        let runtime_origin = if input.peek(Token![<]) {
             input.parse::<Token![<]>()?;
             let ty: Type = input.parse()?;
             input.parse::<Token![>]>()?;
-            ty
+            Some(ty)
+        } else {
+            None
         };
-        let mut content;
+        let content;
         let _paren_token = syn::parenthesized!(content in input);
-        let args = content.parse_terminated(Expr::parse)?;
+        let args = content.parse_terminated(Expr::parse, Token![,])?;
 
         Ok(ExtrinsicCall {
             attribute,
-            underscore,
+            name,
             runtime_origin,
             args,
         })
@@ -36,14 +49,82 @@ impl Parse for ExtrinsicCall {
 
 impl ToTokens for ExtrinsicCall {
     fn to_tokens(&self, tokens: &mut TokenStream) {
+        if !self
+            .attribute
+            .iter()
+            .any(|attr| attr.path().is_ident("extrinsic_call"))
+        {
+            tokens.extend(quote!(#[extrinsic_call]));
+        }
         for attr in &self.attribute {
             attr.to_tokens(tokens);
         }
-        self.underscore.to_tokens(tokens);
-        self.runtime_origin.to_tokens(tokens);
+        match &self.name {
+            CallName::Underscore(underscore) => underscore.to_tokens(tokens),
+            CallName::Named(name) => name.to_tokens(tokens),
+        }
+        // v2 has no turbofish for the origin, it is cast on the first argument instead
         syn::token::Paren::default().surround(tokens, |tokens| {
-            self.args.to_tokens(tokens);
+            for (i, arg) in self.args.iter().enumerate() {
+                if i > 0 {
+                    <Token![,]>::default().to_tokens(tokens);
+                }
+                arg.to_tokens(tokens);
+                if let (0, Some(runtime_origin)) = (i, &self.runtime_origin) {
+                    <Token![as]>::default().to_tokens(tokens);
+                    runtime_origin.to_tokens(tokens);
+                }
+            }
         });
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extrinsic_call_casts_origin() {
+        let call: ExtrinsicCall =
+            syn::parse_str("#[extrinsic_call] _<T::RuntimeOrigin>(origin, account)").unwrap();
+        let expected = quote!(
+            #[extrinsic_call]
+            _(origin as T::RuntimeOrigin, account)
+        );
+        assert_eq!(quote!(#call).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_extrinsic_call_forms() {
+        let call: ExtrinsicCall = syn::parse_str(
+            "_(\n\tRawOrigin::Signed(caller.clone()),\n\tBox::new(create_identity_info::<T>(x)),\n)",
+        )
+        .unwrap();
+        let expected = quote!(
+            #[extrinsic_call]
+            _(
+                RawOrigin::Signed(caller.clone()),
+                Box::new(create_identity_info::<T>(x))
+            )
+        );
+        assert_eq!(quote!(#call).to_string(), expected.to_string());
+
+        let call: ExtrinsicCall =
+            syn::parse_str("set_subs(RawOrigin::Signed(caller), subs)").unwrap();
+        let expected = quote!(
+            #[extrinsic_call]
+            set_subs(RawOrigin::Signed(caller), subs)
+        );
+        assert_eq!(quote!(#call).to_string(), expected.to_string());
+
+        let call: ExtrinsicCall = syn::parse_str("_<T::RuntimeOrigin>()").unwrap();
+        assert_eq!(
+            quote!(#call).to_string(),
+            quote!(
+                #[extrinsic_call]
+                _()
+            )
+            .to_string()
+        );
+    }
+}
@@ -1,4 +1,4 @@
 pub(crate) mod benchmark;
 pub(crate) mod block;
-pub(crate) mod param;
 pub(crate) mod extrinsic;
+pub(crate) mod param;
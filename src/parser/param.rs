@@ -1,141 +1,377 @@
 use nom::branch::alt;
-use nom::combinator::{map_res, recognize, value};
-use nom::multi::{many0_count, many_till};
+use nom::combinator::{opt, recognize, verify};
 use nom::{
-    bytes::complete::{tag, take_until, take_while_m_n},
-    character::complete::{
-        alpha1, alphanumeric1, anychar, char, digit1, multispace0, multispace1, u8 as nom_u8,
-    },
-    combinator::{cut, map},
-    error::ParseError,
-    sequence::{preceded, terminated, tuple},
-    Err as NomErr, IResult,
+    bytes::complete::{tag, take_until},
+    character::complete::{alpha1, alphanumeric1, char, multispace0},
+    combinator::map,
+    multi::many0_count,
+    sequence::{pair, preceded},
+    IResult,
 };
 
-use crate::lexer::{BenchmarkLine, LineKind};
 use anyhow::{anyhow, Result};
+use proc_macro2::{Delimiter, Ident, Spacing, TokenStream, TokenTree};
+use std::collections::HashSet;
+use std::ops::Range;
+use tracing::trace;
 
 pub struct ParamParser;
 pub struct ParamWriter;
+
+// The binding pattern being read by `ParamWriter::collect_identifiers`.
+#[derive(Clone, Copy, PartialEq)]
+enum Pattern {
+    Let,
+    For,
+    Closure,
+}
 #[derive(Debug, Clone)]
 pub struct BenchmarkParameter {
     pub name: String,
-    pub range_start: u8,
+    pub range_start: String,
     pub range_end: String,
+    /// Code following `=>`, run before the benchmark body. `None` for `=> ()`.
+    pub setup: Option<String>,
+}
+
+/// A top-level part of a benchmark body, in source order.
+pub enum BodyPart {
+    /// A component declaration including its trailing `;`.
+    Component(Vec<TokenTree>),
+    /// The statements between component declarations.
+    Code(TokenStream),
 }
 
 impl Default for BenchmarkParameter {
     fn default() -> Self {
         Self {
             name: "".to_string(),
-            range_start: 0,
+            range_start: "".to_string(),
             range_end: "".to_string(),
+            setup: None,
         }
     }
 }
 
 impl ParamParser {
-    pub fn dispatch(input: &str) -> Result<BenchmarkLine> {
-        if input.trim_start().starts_with("let ") {
-            if input.trim_start().contains("=") && !input.trim_start().contains("=>") {
-                Ok(BenchmarkLine {
-                    head: None,
-                    kind: LineKind::Content,
-                    content: Some(input.to_string()),
-                    param_content: None,
-                    fn_body: None,
-                })
-            } else {
-                match Self::let_declaration(input) {
-                    Ok((_str, param)) => {
-                        let param = BenchmarkParameter {
-                            name: param.name,
-                            range_start: param.range_start,
-                            range_end: param.range_end,
-                        };
-                        Ok(BenchmarkLine {
-                            head: None,
-                            kind: LineKind::FnParam,
-                            content: None,
-                            param_content: Some(param),
-                            fn_body: None,
-                        })
-                    }
-                    Err(e) => Err(anyhow!("Error parsing parameter: {:?}", e)),
-                }
-            }
-        } else {
-            Err(anyhow!("Error parsing parameter"))
-        }
-    }
-
     pub fn let_declaration(input: &str) -> IResult<&str, BenchmarkParameter> {
         let (input, _) = tag("let ")(input.trim())?;
-        let (input, name) = recognize(alpha1)(input)?;
+        let (input, name) = Self::identifier(input)?;
         let (input, _) = multispace0(input)?;
         let (input, _) = tag("in")(input)?;
         let (input, _) = multispace0(input)?;
 
         let (input, range_start_val) =
-            map_res(digit1, |digit_str: &str| digit_str.parse::<u8>())(input)?;
-        let (input, _) = multispace0(input)?;
+            verify(take_until(".."), |start: &str| !start.trim().is_empty())(input)?;
+        let range_start_val = range_start_val.trim();
 
         // Directly capture the range end after '..'
         let (input, range_end_val) = Self::range_end(input)?;
-        println!(
-            "name: {:?}, range_start: {:?}, range_end: {:?}",
-            name, range_start_val, range_end_val
+        let (input, setup) =
+            alt((preceded(tag("=>"), Self::setup), map(char(';'), |_| None)))(input)?;
+        trace!(
+            name,
+            range_start_val,
+            range_end_val,
+            ?setup,
+            "parsed component"
         );
 
         Ok((
             input,
             BenchmarkParameter {
                 name: name.to_string(),
-                range_start: range_start_val,
+                range_start: range_start_val.to_string(),
                 range_end: range_end_val.trim().to_string(),
+                setup,
             },
         ))
     }
 
-    fn range_end(input: &str) -> IResult<&str, String> {
+    // Anything shaped like an identifier, keywords are only rejected in the signature.
+    fn identifier(input: &str) -> IResult<&str, &str> {
+        recognize(pair(
+            opt(tag("r#")),
+            pair(
+                alt((alpha1, tag("_"))),
+                many0_count(alt((alphanumeric1, tag("_")))),
+            ),
+        ))(input)
+    }
+
+    /// Splits a benchmark body into component declarations
+    /// `let <name> in <from> .. <to> => <setup>;` and the statements around them.
+    pub fn split_body(tokens: TokenStream) -> Vec<BodyPart> {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        let mut parts = Vec::new();
+        let mut code = Vec::new();
+        let mut index = 0;
+        while index < tokens.len() {
+            let remaining = &tokens[index..];
+            let is_component = matches!(
+                remaining,
+                [TokenTree::Ident(keyword), TokenTree::Ident(_), TokenTree::Ident(op), ..]
+                    if keyword == "let" && op == "in"
+            );
+            if is_component {
+                let end = remaining
+                    .iter()
+                    .position(Self::is_semicolon)
+                    .map_or(remaining.len(), |semi| semi + 1);
+                if !code.is_empty() {
+                    parts.push(BodyPart::Code(code.drain(..).collect()));
+                }
+                parts.push(BodyPart::Component(remaining[..end].to_vec()));
+                index += end;
+            } else {
+                code.push(remaining[0].clone());
+                index += 1;
+            }
+        }
+        if !code.is_empty() {
+            parts.push(BodyPart::Code(code.into_iter().collect()));
+        }
+        parts
+    }
+
+    pub(crate) fn is_semicolon(token: &TokenTree) -> bool {
+        matches!(token, TokenTree::Punct(semi) if semi.as_char() == ';')
+    }
+
+    // The upper bound ends at `=>` if the component has setup code, otherwise at `;`.
+    fn range_end(input: &str) -> IResult<&str, &str> {
         let (input, _) = tag("..")(input)?;
         alt((
-            map(
-                terminated(recognize(take_until(";")), char(';')),
-                |s: &str| s.trim().to_string(),
-            ),
-            map(
-                terminated(recognize(take_until("=>")), tag("=>")),
-                |s: &str| s.trim().to_string(),
-            ),
+            verify(take_until("=>"), |end: &str| !end.contains(';')),
+            take_until(";"),
         ))(input)
     }
+
+    // Everything after `=>` up to the closing `;`, unit setups are dropped.
+    fn setup(input: &str) -> IResult<&str, Option<String>> {
+        let setup = input.trim().trim_end_matches(';').trim();
+        let is_unit = matches!(
+            setup.split_whitespace().collect::<String>().as_str(),
+            "" | "()" | "{}"
+        );
+        Ok(("", (!is_unit).then(|| setup.to_string())))
+    }
 }
 
 impl ParamWriter {
-    pub fn fn_input(param: &BenchmarkParameter) -> String {
-        let range_end_expression = param.range_end.split("=>").next().unwrap_or("").trim();
+    /// Fills `_` bounds and setup from the common components of a `_ { ... }` block, and
+    /// adds the common components `code` refers to without declaring them.
+    pub fn resolve_common(
+        params: &[BenchmarkParameter],
+        common: &[BenchmarkParameter],
+        code: &[&str],
+    ) -> Result<Vec<BenchmarkParameter>> {
+        let mut resolved = Vec::with_capacity(params.len());
+        for param in params {
+            let is_placeholder = |part: &str| part == "_";
+            if !is_placeholder(&param.range_start)
+                && !is_placeholder(&param.range_end)
+                && param.setup.as_deref() != Some("_")
+            {
+                resolved.push(param.clone());
+                continue;
+            }
+            let shared = common
+                .iter()
+                .find(|shared| shared.name == param.name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Component `{}` refers to a common component that is not declared",
+                        param.name
+                    )
+                })?;
+            resolved.push(BenchmarkParameter {
+                name: param.name.clone(),
+                range_start: Self::or_common(&param.range_start, &shared.range_start),
+                range_end: Self::or_common(&param.range_end, &shared.range_end),
+                setup: match param.setup.as_deref() {
+                    Some("_") => shared.setup.clone(),
+                    _ => param.setup.clone(),
+                },
+            });
+        }
 
-        // Determine if range_end is a numeric constant or an expression.
-        let is_numeric_constant = range_end_expression.parse::<u64>().is_ok();
+        // The pieces share one scope in v1, so a `let` in one shadows the name in all.
+        let mut tokens = TokenStream::new();
+        for code in code {
+            let code: TokenStream = code
+                .parse()
+                .map_err(|e| anyhow!("Error tokenizing benchmark code: {}", e))?;
+            tokens.extend(code);
+        }
+        let identifiers = Self::free_identifiers(tokens);
+        for shared in common {
+            let declared = params.iter().any(|param| param.name == shared.name);
+            if !declared && identifiers.contains(&shared.name) {
+                resolved.push(shared.clone());
+            }
+        }
+        Ok(resolved)
+    }
 
-        // For numeric constants, we don't add curly braces. For expressions, we do.
-        let range_end = if is_numeric_constant {
-            range_end_expression.to_string()
-        } else {
-            format!("{{ {} }}", range_end_expression)
+    fn or_common(part: &str, common: &str) -> String {
+        if part == "_" { common } else { part }.to_string()
+    }
+
+    /// Identifiers `tokens` uses as variables: not fields, methods or path segments, and
+    /// not bound anywhere in `tokens` by a `let`, a `for`, a `match` arm or a closure parameter.
+    pub(crate) fn free_identifiers(tokens: TokenStream) -> HashSet<String> {
+        let mut used = HashSet::new();
+        let mut bound = HashSet::new();
+        Self::collect_identifiers(tokens, &mut used, &mut bound);
+        used.retain(|name| !bound.contains(name));
+        used
+    }
+
+    // Sorts the identifiers of `tokens` into variable uses and names bound by a pattern.
+    fn collect_identifiers(
+        tokens: TokenStream,
+        used: &mut HashSet<String>,
+        bound: &mut HashSet<String>,
+    ) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        let is_punct = |index: Option<usize>, c: char| match index.and_then(|i| tokens.get(i)) {
+            Some(TokenTree::Punct(punct)) => punct.as_char() == c,
+            _ => false,
         };
+        let mut pattern: Option<Pattern> = None;
+        // Set between `match` and the braces holding its arms
+        let mut in_match = false;
+        for (index, token) in tokens.iter().enumerate() {
+            let prev = index.checked_sub(1);
+            match token {
+                TokenTree::Ident(ident) if pattern.is_none() && ident == "match" => in_match = true,
+                TokenTree::Group(group) if in_match && group.delimiter() == Delimiter::Brace => {
+                    in_match = false;
+                    Self::collect_arms(group.stream(), used, bound);
+                }
+                TokenTree::Ident(ident) if ident == "let" => pattern = Some(Pattern::Let),
+                TokenTree::Ident(ident) if ident == "for" => pattern = Some(Pattern::For),
+                TokenTree::Ident(ident) if pattern == Some(Pattern::For) && ident == "in" => {
+                    pattern = None
+                }
+                TokenTree::Ident(ident) => {
+                    if pattern.is_some() {
+                        bound.insert(ident.to_string());
+                    } else if !['.', ':', '\''].iter().any(|&c| is_punct(prev, c))
+                        && !is_punct(Some(index + 1), ':')
+                    {
+                        used.insert(ident.to_string());
+                    }
+                }
+                TokenTree::Group(group) if pattern.is_some() => {
+                    Self::collect_identifiers(group.stream(), bound, &mut HashSet::new())
+                }
+                TokenTree::Group(group) => Self::collect_identifiers(group.stream(), used, bound),
+                TokenTree::Punct(punct) => match (pattern, punct.as_char()) {
+                    (Some(Pattern::Let), '=' | ';') | (Some(Pattern::Closure), '|') => {
+                        pattern = None
+                    }
+                    // A type annotation ends the pattern, a path separator does not.
+                    (Some(Pattern::Let), ':')
+                        if punct.spacing() == Spacing::Alone && !is_punct(prev, ':') =>
+                    {
+                        pattern = None
+                    }
+                    // `||` is either an empty closure or a logical or.
+                    (None, '|') if !is_punct(Some(index + 1), '|') && !is_punct(prev, '|') => {
+                        let starts_closure = match prev.map(|prev| &tokens[prev]) {
+                            None => true,
+                            Some(TokenTree::Punct(punct)) => matches!(punct.as_char(), ',' | '='),
+                            Some(TokenTree::Ident(ident)) => ident == "move",
+                            _ => false,
+                        };
+                        if starts_closure {
+                            pattern = Some(Pattern::Closure);
+                        }
+                    }
+                    _ => {}
+                },
+                TokenTree::Literal(_) => {}
+            }
+        }
+    }
+
+    // Sorts the identifiers of the arms of a `match`: the pattern in front of `=>` binds,
+    // while a guard and the arm body use.
+    fn collect_arms(tokens: TokenStream, used: &mut HashSet<String>, bound: &mut HashSet<String>) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        let stream = |range: Range<usize>| tokens[range].iter().cloned().collect::<TokenStream>();
+        let is_punct = |index: usize, c: char| matches!(tokens.get(index), Some(TokenTree::Punct(punct)) if punct.as_char() == c);
+        let mut start = 0;
+        while start < tokens.len() {
+            let arrow = (start..tokens.len())
+                .find(|&index| is_punct(index, '=') && is_punct(index + 1, '>'))
+                .unwrap_or(tokens.len());
+            let guard = (start..arrow)
+                .find(|&index| matches!(&tokens[index], TokenTree::Ident(ident) if ident == "if"))
+                .unwrap_or(arrow);
+            Self::collect_identifiers(stream(start..guard), bound, &mut HashSet::new());
+            Self::collect_identifiers(stream(guard..arrow), used, bound);
+            // A body in braces ends the arm, any other body ends at the next comma
+            let body = (arrow + 2).min(tokens.len());
+            let end = match tokens.get(body) {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => body + 1,
+                _ => (body..tokens.len())
+                    .find(|&index| is_punct(index, ','))
+                    .unwrap_or(tokens.len()),
+            };
+            Self::collect_identifiers(stream(body..end), used, bound);
+            start = if is_punct(end, ',') { end + 1 } else { end };
+        }
+    }
 
+    /// Rejects component names that cannot all be parameters of the same function.
+    pub fn check_names(params: &[BenchmarkParameter]) -> Result<()> {
+        let mut seen = HashSet::new();
+        for param in params {
+            if syn::parse_str::<Ident>(&param.name).is_err() {
+                return Err(anyhow!(
+                    "Component `{}` is a reserved word and cannot name a parameter",
+                    param.name
+                ));
+            }
+            // `r#name` and `name` are the same parameter
+            if !seen.insert(param.name.trim_start_matches("r#")) {
+                return Err(anyhow!(
+                    "Component `{}` is declared more than once",
+                    param.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn fn_input(param: &BenchmarkParameter) -> String {
         // Format the parameter string.
         format!(
             "{}: Linear<{}, {}>,",
-            param.name, param.range_start, range_end
+            param.name,
+            Self::bound(&param.range_start),
+            Self::bound(&param.range_end)
         )
     }
 
+    // Numeric literals and constants named by a single identifier are valid const
+    // arguments as they are, other expressions need braces.
+    fn bound(expression: &str) -> String {
+        if expression.parse::<u64>().is_ok() || syn::parse_str::<Ident>(expression).is_ok() {
+            expression.to_string()
+        } else {
+            format!("{{ {} }}", expression)
+        }
+    }
+
     pub fn fn_gen(param_input: String, fn_signature: &String) -> Result<String> {
-        println!("fn_gen: {:?}, {:?}", param_input, fn_signature);
-        if let Some(open_paren_pos) = fn_signature.find('(') {
+        trace!(%param_input, %fn_signature, "adding components to signature");
+        // Skip the attribute line, whose arguments have parentheses of their own
+        let fn_pos = fn_signature.find("\nfn ").unwrap_or(0);
+        if let Some(open_paren_pos) = fn_signature[fn_pos..].find('(').map(|pos| fn_pos + pos) {
             if let Some(close_paren_pos) = fn_signature[open_paren_pos..].find(')') {
                 let close_paren_pos = open_paren_pos + close_paren_pos;
 
@@ -160,9 +396,9 @@ mod tests {
     fn test_parse_param_declaration_with_expression() {
         let input = "let r in 1 .. T::MaxRegistrars::get() =>";
         match ParamParser::let_declaration(input) {
-            Ok((str, param)) => {
+            Ok((_str, param)) => {
                 assert_eq!(param.name, "r");
-                assert_eq!(param.range_start, 1);
+                assert_eq!(param.range_start, "1");
                 assert_eq!(param.range_end, "T::MaxRegistrars::get()");
             }
             Err(e) => panic!("Parsing failed when it should have succeeded: {:?}", e),
@@ -173,15 +409,56 @@ mod tests {
     fn test_parse_param_declaration_with_constant() {
         let input = "let b in 1 .. MAX_BYTES;";
         match ParamParser::let_declaration(input) {
-            Ok((str, param)) => {
+            Ok((_str, param)) => {
                 assert_eq!(param.name, "b");
-                assert_eq!(param.range_start, 1);
+                assert_eq!(param.range_start, "1");
                 assert_eq!(param.range_end, "MAX_BYTES");
             }
             Err(e) => panic!("Parsing failed when it should have succeeded: {:?}", e),
         }
     }
 
+    #[test]
+    fn test_parse_param_declaration_with_expression_start() {
+        let (_, param) = ParamParser::let_declaration("let b in 1000 .. MAX_BYTES;").unwrap();
+        assert_eq!(param.range_start, "1000");
+        let (_, param) =
+            ParamParser::let_declaration("let m in T::MinMembers::get() .. T::MaxMembers::get();")
+                .unwrap();
+        assert_eq!(param.range_start, "T::MinMembers::get()");
+        assert_eq!(param.range_end, "T::MaxMembers::get()");
+        assert!(ParamParser::let_declaration("let m in .. 10;").is_err());
+    }
+
+    #[test]
+    fn test_parse_param_declaration_names() {
+        for name in ["n_proposals", "x1", "max_len", "_unused", "r#type", "type"] {
+            let input = format!("let {} in 0 .. 10;", name);
+            let (_, param) = ParamParser::let_declaration(&input).unwrap();
+            assert_eq!(param.name, name);
+        }
+        assert!(ParamParser::let_declaration("let 1x in 0 .. 10;").is_err());
+    }
+
+    #[test]
+    fn test_check_names() {
+        let param = |name: &str| BenchmarkParameter {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        assert!(ParamWriter::check_names(&[param("n_proposals"), param("r#type")]).is_ok());
+        let error = ParamWriter::check_names(&[param("type")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Component `type` is a reserved word and cannot name a parameter"
+        );
+        let error = ParamWriter::check_names(&[param("r#n"), param("n")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Component `n` is declared more than once"
+        );
+    }
+
     #[test]
     fn test_invalid_param_declaration_no_range() {
         let input = "let foo =";
@@ -192,27 +469,157 @@ mod tests {
         );
     }
 
-    //#[test]
+    #[test]
+    fn test_split_body_keeps_order() {
+        let body: TokenStream = "let caller = whitelisted_caller();\n let x in 0 .. 10;\n let r in 1 .. T::Max::get() => add(r);\n fund(&caller);"
+            .parse()
+            .unwrap();
+        let parts: Vec<String> = ParamParser::split_body(body)
+            .into_iter()
+            .map(|part| match part {
+                BodyPart::Component(tokens) => {
+                    format!("component {}", TokenStream::from_iter(tokens))
+                }
+                BodyPart::Code(code) => format!("code {}", code),
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                "code let caller = whitelisted_caller () ;",
+                "component let x in 0 .. 10 ;",
+                "component let r in 1 .. T :: Max :: get () => add (r) ;",
+                "code fund (& caller) ;",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_param_setup() {
+        let (_, param) =
+            ParamParser::let_declaration("let r in 1 .. T::Max::get() => add::<T>(r)?;").unwrap();
+        assert_eq!(param.range_end, "T::Max::get()");
+        assert_eq!(param.setup.as_deref(), Some("add::<T>(r)?"));
+
+        let block = "let x in 0 .. 10 => {\n\tlet info = info::<T>(x);\n\tset::<T>(info)?;\n};";
+        let (_, param) = ParamParser::let_declaration(block).unwrap();
+        assert_eq!(param.range_end, "10");
+        assert_eq!(
+            param.setup.as_deref(),
+            Some("{\n\tlet info = info::<T>(x);\n\tset::<T>(info)?;\n}")
+        );
+
+        for unit in [
+            "let x in 0 .. 10 => ();",
+            "let x in 0 .. 10 => ( );",
+            "let x in 0 .. 10;",
+        ] {
+            let (_, param) = ParamParser::let_declaration(unit).unwrap();
+            assert_eq!(param.range_end, "10");
+            assert!(param.setup.is_none(), "{}", unit);
+        }
+    }
+
+    #[test]
+    fn test_resolve_common() {
+        let common = [
+            BenchmarkParameter {
+                name: "l".to_string(),
+                range_start: "1".to_string(),
+                range_end: "MAX_LENGTH".to_string(),
+                setup: Some("init::<T>(l)".to_string()),
+            },
+            BenchmarkParameter {
+                name: "u".to_string(),
+                range_start: "0".to_string(),
+                range_end: "1000".to_string(),
+                setup: None,
+            },
+            BenchmarkParameter {
+                name: "e".to_string(),
+                range_start: "0".to_string(),
+                range_end: "10".to_string(),
+                setup: None,
+            },
+            BenchmarkParameter {
+                name: "p".to_string(),
+                range_start: "1".to_string(),
+                range_end: "100".to_string(),
+                setup: None,
+            },
+        ];
+        let (_, placeholder) = ParamParser::let_declaration("let l in _ .. _ => _;").unwrap();
+        let (_, own) = ParamParser::let_declaration("let e in 2 .. 5;").unwrap();
+        let resolved = ParamWriter::resolve_common(
+            &[placeholder, own],
+            &common,
+            &["let who = account::<T>(u);", "_(RawOrigin::Signed(who))"],
+        )
+        .unwrap();
+        let resolved: Vec<String> = resolved
+            .iter()
+            .map(|param| {
+                format!(
+                    "{} {}..{} {:?}",
+                    param.name, param.range_start, param.range_end, param.setup
+                )
+            })
+            .collect();
+        assert_eq!(
+            resolved,
+            [
+                "l 1..MAX_LENGTH Some(\"init::<T>(l)\")",
+                "e 2..5 None",
+                "u 0..1000 None"
+            ]
+        );
+
+        let resolved = ParamWriter::resolve_common(
+            &[],
+            &common,
+            &[
+                "let l = T::MaxLength::get(); let who = account::<T>(0);",
+                "_(RawOrigin::Signed(who), l, info.e)",
+                "let _ = e::decode(); vec![1; u as usize].iter().map(|e| e + 1);",
+                "match who.p { Some(p) if p > e => { ensure!(p > 1, \"p\"); } Err(ref p) => drop(p), }",
+            ],
+        )
+        .unwrap();
+        let names: Vec<&str> = resolved.iter().map(|param| param.name.as_str()).collect();
+        assert_eq!(names, ["u"]);
+
+        let (_, unknown) = ParamParser::let_declaration("let x in _ .. _ => ();").unwrap();
+        let error = ParamWriter::resolve_common(&[unknown], &common, &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Component `x` refers to a common component that is not declared"
+        );
+    }
+
+    #[test]
     fn test_writer_fn_input() {
-        let params = vec![
+        let params = [
             BenchmarkParameter {
                 name: "b".to_string(),
-                range_start: 1,
+                range_start: "1".to_string(),
                 range_end: "MAX_BYTES".to_string(),
+                ..Default::default()
             },
             BenchmarkParameter {
                 name: "m".to_string(),
-                range_start: 2,
+                range_start: "2".to_string(),
                 range_end: "T::MaxFellows::get()".to_string(),
+                ..Default::default()
             },
             BenchmarkParameter {
                 name: "p".to_string(),
-                range_start: 1,
+                range_start: "1".to_string(),
                 range_end: "T::MaxProposals::get()".to_string(),
+                ..Default::default()
             },
         ];
 
-        let expected_outputs = vec![
+        let expected_outputs = [
             "b: Linear<1, MAX_BYTES>,",
             "m: Linear<2, { T::MaxFellows::get() }>,",
             "p: Linear<1, { T::MaxProposals::get() }>,",
@@ -222,4 +629,38 @@ mod tests {
             assert_eq!(ParamWriter::fn_input(param), *expected);
         }
     }
+
+    #[test]
+    fn test_writer_fn_input_with_expression_start() {
+        let params = [
+            BenchmarkParameter {
+                name: "b".to_string(),
+                range_start: "1000".to_string(),
+                range_end: "100".to_string(),
+                ..Default::default()
+            },
+            BenchmarkParameter {
+                name: "m".to_string(),
+                range_start: "T::MinFellows::get()".to_string(),
+                range_end: "T::MaxFellows::get()".to_string(),
+                ..Default::default()
+            },
+            BenchmarkParameter {
+                name: "l".to_string(),
+                range_start: "MIN_LENGTH + 1".to_string(),
+                range_end: "MAX_LENGTH * 2".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let expected_outputs = [
+            "b: Linear<1000, 100>,",
+            "m: Linear<{ T::MinFellows::get() }, { T::MaxFellows::get() }>,",
+            "l: Linear<{ MIN_LENGTH + 1 }, { MAX_LENGTH * 2 }>,",
+        ];
+
+        for (param, expected) in params.iter().zip(expected_outputs.iter()) {
+            assert_eq!(ParamWriter::fn_input(param), *expected);
+        }
+    }
 }
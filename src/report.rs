@@ -0,0 +1,168 @@
+use crate::writer::GeneratedModule;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::path::Path;
+
+/// Version of the report layout. Bumped whenever a field is renamed, removed or changes
+/// meaning; new fields may be added without a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+}
+
+/// Conversion report covering every file of one invocation of the tool.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub schema_version: u32,
+    pub files: Vec<FileReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    /// The file converted and none of its benchmarks were skipped.
+    pub success: bool,
+    /// Why the file could not be converted at all.
+    pub error: Option<String>,
+    pub benchmarks: Vec<BenchmarkReport>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub name: String,
+    pub components: Vec<ComponentReport>,
+    /// The extrinsic call as written after `}:`, or the `{ ... }` block measured instead.
+    pub extrinsic: Option<String>,
+    /// Whether the benchmark is part of the generated module.
+    pub converted: bool,
+}
+
+/// A component declared with `let <name> in <start> .. <end>`, bounds as source text.
+#[derive(Debug, Serialize)]
+pub struct ComponentReport {
+    pub name: String,
+    pub start: String,
+    pub end: String,
+}
+
+impl Report {
+    pub fn new(files: Vec<FileReport>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            files,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize report")
+    }
+}
+
+impl FileReport {
+    pub fn new(path: &Path, result: &Result<GeneratedModule>) -> Self {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let generated = match result {
+            Ok(generated) => generated,
+            Err(e) => {
+                return Self {
+                    path,
+                    success: false,
+                    error: Some(format!("{:#}", e)),
+                    benchmarks: Vec::new(),
+                    warnings: Vec::new(),
+                }
+            }
+        };
+        let benchmarks = generated
+            .benchmarks
+            .iter()
+            .map(|benchmark| BenchmarkReport {
+                name: benchmark.name.clone(),
+                components: benchmark
+                    .params
+                    .iter()
+                    .map(|param| ComponentReport {
+                        name: param.name.clone(),
+                        start: param.range_start.clone(),
+                        end: param.range_end.clone(),
+                    })
+                    .collect(),
+                extrinsic: benchmark
+                    .extrinsic
+                    .as_deref()
+                    .map(|call| call.trim().to_string()),
+                converted: !generated
+                    .skipped
+                    .iter()
+                    .any(|skipped| skipped.name == benchmark.name),
+            })
+            .collect();
+        let warnings = generated
+            .skipped
+            .iter()
+            .map(|skipped| format!("skipped benchmark `{}`: {}", skipped.name, skipped.reason))
+            .collect();
+        Self {
+            path,
+            success: generated.skipped.is_empty(),
+            error: None,
+            benchmarks,
+            warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::lexer::Lexer;
+    use crate::writer::Writer;
+    use anyhow::anyhow;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_report_layout() {
+        let input = r#"benchmarks! {
+	add_registrar {
+		let r in 1 .. T::MaxRegistrars::get() - 1 => add_registrars::<T>(r)?;
+		let account = T::Lookup::unlookup(account("registrar", r + 1, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+	set_identity {
+		let r in 1 .. 10;
+	}
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let generated = Writer::generate_module(lines, &Config::default());
+        let report = Report::new(vec![
+            FileReport::new(Path::new("src/benchmarking.rs"), &generated),
+            FileReport::new(Path::new("src/missing.rs"), &Err(anyhow!("Failed to read"))),
+        ]);
+        let value: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+
+        assert_eq!(value["schema_version"], json!(SCHEMA_VERSION));
+        let file = &value["files"][0];
+        assert_eq!(file["path"], "src/benchmarking.rs");
+        assert_eq!(file["success"], false);
+        assert_eq!(file["error"], Value::Null);
+        assert_eq!(
+            file["benchmarks"][0],
+            json!({
+                "name": "add_registrar",
+                "components": [
+                    { "name": "r", "start": "1", "end": "T::MaxRegistrars::get() - 1" }
+                ],
+                "extrinsic": "_<T::RuntimeOrigin>(origin, account)",
+                "converted": true,
+            })
+        );
+        assert_eq!(file["benchmarks"][1]["converted"], false);
+        assert_eq!(file["benchmarks"][1]["extrinsic"], Value::Null);
+        assert_eq!(file["warnings"].as_array().unwrap().len(), 1);
+        assert_eq!(value["files"][1]["error"], "Failed to read");
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Context, Result};
+use similar::TextDiff;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Suffix appended to a file name for the copy of the original v1 source.
+pub const BACKUP_SUFFIX: &str = ".v1.bak";
+
+pub struct Rewriter;
+
+impl Rewriter {
+    pub fn backup_path(path: &Path) -> PathBuf {
+        let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(BACKUP_SUFFIX);
+        path.with_file_name(file_name)
+    }
+
+    /// The inverse of `backup_path`, `None` if `backup` is not a backup file.
+    pub fn original_path(backup: &Path) -> Option<PathBuf> {
+        let file_name = backup.file_name()?.to_str()?;
+        let original = file_name.strip_suffix(BACKUP_SUFFIX)?;
+        Some(backup.with_file_name(original))
+    }
+
+    // Swaps the `benchmarks!` invocation at `span` for the generated module.
+    pub fn replace_invocation(source: &str, span: Range<usize>, module: &str) -> String {
+        format!(
+            "{}{}{}",
+            &source[..span.start],
+            module.trim_end(),
+            &source[span.end..]
+        )
+    }
+
+    /// Renders a unified diff from `original` to `rewritten` with `a/` and `b/` prefixed
+    /// headers, so it applies with `git apply` or `patch -p1` from the directory `path` is
+    /// relative to.
+    pub fn unified_diff(path: &Path, original: &str, rewritten: &str) -> String {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let old_header = format!("a/{}", path);
+        let new_header = format!("b/{}", path);
+        TextDiff::from_lines(original, rewritten)
+            .unified_diff()
+            .context_radius(3)
+            .header(&old_header, &new_header)
+            .to_string()
+    }
+
+    /// Writes `rewritten` over `path`, keeping the original contents next to it.
+    pub fn write_in_place(path: &Path, original: &str, rewritten: &str) -> Result<PathBuf> {
+        let backup = Self::backup_path(path);
+        if backup.exists() {
+            return Err(anyhow!(
+                "Backup {} already exists, run `restore` first",
+                backup.display()
+            ));
+        }
+        fs::write(&backup, original)
+            .with_context(|| format!("Failed to write backup {}", backup.display()))?;
+        fs::write(path, rewritten)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(backup)
+    }
+
+    /// Moves the backup of `path` back in place, undoing `write_in_place`.
+    pub fn restore(path: &Path) -> Result<PathBuf> {
+        let backup = Self::backup_path(path);
+        if !backup.exists() {
+            return Err(anyhow!("No backup found at {}", backup.display()));
+        }
+        fs::rename(&backup, path)
+            .with_context(|| format!("Failed to restore {}", path.display()))?;
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_path() {
+        let path = Path::new("pallets/identity/src/benchmarking.rs");
+        assert_eq!(
+            Rewriter::backup_path(path),
+            PathBuf::from("pallets/identity/src/benchmarking.rs.v1.bak")
+        );
+        assert_eq!(
+            Rewriter::original_path(&Rewriter::backup_path(path)),
+            Some(path.to_path_buf())
+        );
+        assert_eq!(Rewriter::original_path(path), None);
+    }
+
+    #[test]
+    fn test_replace_invocation() {
+        let source = "use super::*;\n\nbenchmarks! { a {} : _() }\n";
+        let span = 15..source.len() - 1;
+        let rewritten =
+            Rewriter::replace_invocation(source, span, "#[benchmarks]\nmod benchmarks {}\n");
+        assert_eq!(
+            rewritten,
+            "use super::*;\n\n#[benchmarks]\nmod benchmarks {}\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let original = "use super::*;\n\nbenchmarks! {\n\ta {}: _()\n}\n\nfn helper() {}\n";
+        let rewritten = "use super::*;\n\n#[benchmarks]\nmod benchmarks {}\n\nfn helper() {}\n";
+        let diff = Rewriter::unified_diff(Path::new("src/benchmarking.rs"), original, rewritten);
+        assert_eq!(
+            diff,
+            "--- a/src/benchmarking.rs\n\
+             +++ b/src/benchmarking.rs\n\
+             @@ -1,7 +1,6 @@\n \
+             use super::*;\n \n\
+             -benchmarks! {\n\
+             -\ta {}: _()\n\
+             -}\n\
+             +#[benchmarks]\n\
+             +mod benchmarks {}\n \n \
+             fn helper() {}\n"
+        );
+        assert!(Rewriter::unified_diff(Path::new("a.rs"), original, original).is_empty());
+    }
+
+    #[test]
+    fn test_write_in_place_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("benchmarking.rs");
+        fs::write(&path, "v1").unwrap();
+
+        let backup = Rewriter::write_in_place(&path, "v1", "v2").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v2");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "v1");
+        assert!(Rewriter::write_in_place(&path, "v2", "v2").is_err());
+
+        Rewriter::restore(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v1");
+        assert!(!backup.exists());
+        assert!(Rewriter::restore(&path).is_err());
+    }
+}
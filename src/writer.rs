@@ -1,76 +1,467 @@
-use crate::lexer::{BenchmarkLine, Lexer, LineKind};
+use crate::config::Config;
+use crate::lexer::Lexer;
+use crate::lexer::{BenchmarkLine, LineKind};
 use crate::parser::{
-    block::{BlockParser, BlockWriter},
-    param::{ParamParser, ParamWriter},
+    block::{BlockWriter, MacroKind},
+    param::{BenchmarkParameter, ParamWriter},
 };
 use anyhow::{anyhow, Result};
-use syn::{parse_str, Item, ItemMod};
+use proc_macro2::{Group, TokenStream, TokenTree};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use syn::{parse_quote, parse_str, File, Item};
+use tracing::{debug, trace};
 
 pub struct Writer;
 
+/// A benchmark that was left out of the generated module.
+#[derive(Debug, Clone)]
+pub struct SkippedBenchmark {
+    pub name: String,
+    pub reason: String,
+}
+
+/// What the lexer found for one benchmark, whether or not it converted.
+#[derive(Debug, Clone)]
+pub struct BenchmarkSummary {
+    pub name: String,
+    pub params: Vec<BenchmarkParameter>,
+    /// The extrinsic call following `}:`, as written in the v1 source.
+    pub extrinsic: Option<String>,
+}
+
+/// The result of converting a `benchmarks!` invocation.
+#[derive(Debug, Clone)]
+pub struct GeneratedModule {
+    /// The formatted v2 `#[benchmarks]` module.
+    pub code: String,
+    /// Every benchmark of the invocation in source order, including skipped ones.
+    pub benchmarks: Vec<BenchmarkSummary>,
+    /// Benchmarks that could not be converted and are missing from `code`.
+    pub skipped: Vec<SkippedBenchmark>,
+}
+
+// The lines collected for one benchmark until the next one starts.
+#[derive(Debug, Default)]
+struct PendingBenchmark {
+    name: String,
+    /// Arguments of the `#[benchmark(...)]` attribute.
+    attributes: Vec<String>,
+    params: Vec<BenchmarkParameter>,
+    body: Option<String>,
+    extrinsic: Option<String>,
+    verify: Option<String>,
+    /// Why a part of the benchmark does not parse.
+    error: Option<String>,
+}
+
+// The source text between the delimiters of a macro invocation in the v1 code.
+#[derive(Debug)]
+struct MacroSource {
+    text: String,
+    /// Leading whitespace of the line the invocation starts on.
+    indent: String,
+    /// Lines of `text`, counted from 0, that continue a multi-line literal.
+    literal_lines: HashSet<usize>,
+}
+
 impl Writer {
     // Generates the entire module with benchmarks from a block of DSL code.
-    pub fn generate_module(lines: Vec<BenchmarkLine>) -> Result<Vec<String>> {
-        let mut gen: Vec<String> = Vec::new();
-        for i in 0..lines.len() {
-            let line = &lines[i];
+    pub fn generate_module(lines: Vec<BenchmarkLine>, config: &Config) -> Result<GeneratedModule> {
+        let mut module: Option<String> = None;
+        let mut pending: Option<PendingBenchmark> = None;
+        let mut test_suite: Option<String> = None;
+        let mut where_clause: Option<String> = None;
+        let mut kind = MacroKind::Benchmarks;
+        let mut skipped: Vec<SkippedBenchmark> = Vec::new();
+        let mut benchmarks: Vec<BenchmarkSummary> = Vec::new();
+        // Components of the `_ { ... }` block, collected while it is the current item
+        let mut common: Vec<BenchmarkParameter> = Vec::new();
+        let mut in_common = false;
+        // Attributes written above the next benchmark
+        let mut attributes: Vec<String> = Vec::new();
+        // Macro invocations as written, to undo the token spacing of the printer
+        let mut macros: HashMap<String, MacroSource> = HashMap::new();
+
+        for line in &lines {
+            for code in [&line.fn_body, &line.content].into_iter().flatten() {
+                if let Ok(tokens) = code.parse() {
+                    Self::collect_macros(code, tokens, &mut macros);
+                }
+            }
             match line.kind {
                 LineKind::Mod => {
-                    if let Some(_head) = &line.head {
-                        println!("\n -> is Mod");
-                        let output = BlockWriter::mod_item();
-                        gen.push(output);
+                    if let Some(head) = &line.head {
+                        kind = MacroKind::from_name(head)
+                            .ok_or_else(|| anyhow!("Unknown benchmarks macro `{}`", head))?;
+                        module = Some(BlockWriter::dispatch_mod(kind, config));
                     }
                 }
+                LineKind::WhereClause => {
+                    where_clause = line.content.clone();
+                }
+                LineKind::Common => {
+                    Self::flush(
+                        &mut module,
+                        pending.take(),
+                        &common,
+                        &mut benchmarks,
+                        &mut skipped,
+                        config,
+                    )?;
+                    in_common = true;
+                }
                 LineKind::Fn => {
                     if let Some(head) = &line.head {
-                        println!("\n -> is Fn");
-                        let output = BlockWriter::fn_item(&head);
-                        gen.push(output);
+                        in_common = false;
+                        Self::flush(
+                            &mut module,
+                            pending.take(),
+                            &common,
+                            &mut benchmarks,
+                            &mut skipped,
+                            config,
+                        )?;
+                        pending = Some(PendingBenchmark {
+                            name: head.to_owned(),
+                            attributes: std::mem::take(&mut attributes),
+                            body: line.fn_body.clone(),
+                            ..Default::default()
+                        });
                     }
                 }
+                LineKind::Attribute => {
+                    attributes.extend(line.content.clone());
+                }
                 LineKind::FnParam => {
-                    if let Some(ref param_content) = line.param_content {
-                        println!("\n -> is FnParam");
-                        println!("\n -> param_content: {:?}", param_content);
-                        let fn_input = ParamWriter::fn_input(&param_content);
-                        if let Some(fn_signature) = gen.last() {
-                            let complete_sig = ParamWriter::fn_gen(fn_input, fn_signature)?;
-                            println!("complete_sig");
-
-                            gen.pop();
-                            gen.push(complete_sig);
-
-                            let ast = Self::parse_vec_to_ast(gen.clone())?;
-                            let fn_mod = BlockWriter::fn_into_mod(ast)?;
-
-                            // the fn_body is in the previous line
-                            if i > 0 {
-                                if let Some(fn_body) = &lines[i - 1].fn_body {
-                                    let valid_block = BlockWriter::clean_code_block(fn_body)?;
-                                    let complete_fn =
-                                        BlockWriter::content_into_fn(fn_mod, valid_block).unwrap();
-                                    gen.push(complete_fn);
-                                }
-                            }
+                    if let Some(param_content) = &line.param_content {
+                        trace!(?param_content, in_common, "collected component");
+                        if let Some(benchmark) = pending.as_mut() {
+                            benchmark.params.push(param_content.clone());
+                        } else if in_common {
+                            common.push(param_content.clone());
                         }
                     }
+                }
+                LineKind::Error => match pending.as_mut() {
+                    Some(benchmark) => benchmark.error = line.content.clone(),
+                    // Common components belong to every benchmark
+                    None => {
+                        return Err(anyhow!(
+                            "{}",
+                            line.content.as_deref().unwrap_or("Invalid component")
+                        ))
+                    }
                 },
+                LineKind::Extrinsic => {
+                    if let Some(benchmark) = pending.as_mut() {
+                        benchmark.extrinsic = line.content.clone();
+                    }
+                }
                 LineKind::Verify => {
-                    if let Some(body) = &line.fn_body {
-                        gen.push(body.to_owned());
+                    if let Some(benchmark) = pending.as_mut() {
+                        benchmark.verify = line.fn_body.clone();
                     }
                 }
-                LineKind::Extrinsic => {
-                    let output = BlockWriter::extrinsic(&line.content.clone().unwrap());
-                    let ast = Self::parse_vec_to_ast(gen.clone())?;
-                    let output = BlockWriter::extrinsic_into_fn(ast, &output)?;
-                    gen.push(output);
+                LineKind::ImplMacro => {
+                    in_common = false;
+                    Self::flush(
+                        &mut module,
+                        pending.take(),
+                        &common,
+                        &mut benchmarks,
+                        &mut skipped,
+                        config,
+                    )?;
+                    test_suite = line.content.clone();
+                }
+            }
+        }
+        Self::flush(
+            &mut module,
+            pending.take(),
+            &common,
+            &mut benchmarks,
+            &mut skipped,
+            config,
+        )?;
+
+        let module = module.ok_or_else(|| anyhow!("No benchmarks! invocation found"))?;
+        let code = Self::render(
+            &module,
+            kind,
+            where_clause.as_deref(),
+            test_suite.as_deref(),
+            &macros,
+            config,
+        )?;
+        debug!(
+            benchmarks = benchmarks.len(),
+            skipped = skipped.len(),
+            "generated module"
+        );
+        Ok(GeneratedModule {
+            code,
+            benchmarks,
+            skipped,
+        })
+    }
+
+    // Writes a finished benchmark into the module. A benchmark that cannot be converted
+    // leaves the module untouched and is recorded as skipped.
+    fn flush(
+        module: &mut Option<String>,
+        benchmark: Option<PendingBenchmark>,
+        common: &[BenchmarkParameter],
+        benchmarks: &mut Vec<BenchmarkSummary>,
+        skipped: &mut Vec<SkippedBenchmark>,
+        config: &Config,
+    ) -> Result<()> {
+        let Some(mut benchmark) = benchmark else {
+            return Ok(());
+        };
+        let current = module
+            .as_ref()
+            .ok_or_else(|| anyhow!("Benchmark `{}` outside of benchmarks!", benchmark.name))?;
+        let code: Vec<&str> = [&benchmark.body, &benchmark.extrinsic, &benchmark.verify]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let resolved = ParamWriter::resolve_common(&benchmark.params, common, &code);
+        if let Ok(params) = &resolved {
+            benchmark.params = params.clone();
+        }
+        benchmarks.push(BenchmarkSummary {
+            name: benchmark.name.clone(),
+            params: benchmark.params.clone(),
+            extrinsic: benchmark.extrinsic.clone(),
+        });
+        let converted = match &benchmark.error {
+            Some(error) => Err(anyhow!("{}", error)),
+            None => resolved.and_then(|_| Self::benchmark_into_mod(current, &benchmark, config)),
+        };
+        match converted {
+            Ok(updated) => {
+                debug!(benchmark = %benchmark.name, "converted benchmark");
+                *module = Some(updated);
+            }
+            Err(e) => {
+                debug!(benchmark = %benchmark.name, reason = %e, "skipped benchmark");
+                skipped.push(SkippedBenchmark {
+                    name: benchmark.name,
+                    reason: e.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn benchmark_into_mod(
+        module: &str,
+        benchmark: &PendingBenchmark,
+        config: &Config,
+    ) -> Result<String> {
+        let body = benchmark
+            .body
+            .as_ref()
+            .ok_or_else(|| anyhow!("Benchmark body not found"))?;
+        let extrinsic = benchmark
+            .extrinsic
+            .as_ref()
+            .ok_or_else(|| anyhow!("No extrinsic call found"))?;
+
+        ParamWriter::check_names(&benchmark.params)?;
+        let fn_input: String = benchmark.params.iter().map(ParamWriter::fn_input).collect();
+        let complete_sig = ParamWriter::fn_gen(
+            fn_input,
+            &BlockWriter::fn_item(&benchmark.name, &benchmark.attributes, config),
+        )?;
+
+        let ast = Self::parse_vec_to_ast(vec![module.to_string(), complete_sig])?;
+        let fn_mod = BlockWriter::fn_into_mod(ast)?;
+        let valid_block = BlockWriter::clean_code_block(body, &benchmark.params)?;
+        let complete_fn = BlockWriter::content_into_fn(fn_mod, valid_block)?;
+
+        let ast = Self::parse_vec_to_ast(vec![complete_fn])?;
+        let with_extrinsic =
+            BlockWriter::extrinsic_into_fn(ast, extrinsic, benchmark.verify.as_deref(), config)?;
+
+        let verify = benchmark
+            .verify
+            .as_deref()
+            .map(|verify| BlockWriter::clean_code_block(verify, &[]))
+            .transpose()?;
+        let ast = Self::parse_vec_to_ast(vec![with_extrinsic])?;
+        BlockWriter::verify_into_fn(ast, verify, config)
+    }
+
+    // Adds the where clause, module preamble and test suite, then pretty prints the module
+    // with the configured indentation. Macro invocations keep their v1 source text.
+    fn render(
+        module: &str,
+        kind: MacroKind,
+        where_clause: Option<&str>,
+        test_suite: Option<&str>,
+        macros: &HashMap<String, MacroSource>,
+        config: &Config,
+    ) -> Result<String> {
+        let mut module = match parse_str::<Item>(module)? {
+            Item::Mod(module) => module,
+            _ => return Err(anyhow!("Generated code is not a module")),
+        };
+
+        if let Some((_, items)) = &mut module.content {
+            items.insert(
+                0,
+                parse_quote!(
+                    use super::*;
+                ),
+            );
+            if let Some(test_suite) = test_suite {
+                items.push(parse_str::<Item>(test_suite)?);
+            }
+        }
+        let file = File {
+            shebang: None,
+            attrs: Vec::new(),
+            items: vec![Item::Mod(module)],
+        };
+        let mut code = Self::restore_macros(&prettyplease::unparse(&file), macros)?;
+        if kind == MacroKind::InstancePallet {
+            code = BlockWriter::instance_generics(&code)?;
+        }
+        if let Some(predicates) = where_clause {
+            code = BlockWriter::where_clause_into_mod(&code, predicates)?;
+        }
+        Ok(config.indent_code(&code))
+    }
+
+    // Records the arguments of every `name!(...)` in `tokens`, which were read from `code`.
+    fn collect_macros(code: &str, tokens: TokenStream, macros: &mut HashMap<String, MacroSource>) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            let TokenTree::Group(group) = token else {
+                continue;
+            };
+            if let Some(key) = Self::macro_key(&tokens, index) {
+                let start = group.span_open().end();
+                let text = &code[Lexer::byte_offset(code, start)
+                    ..Lexer::byte_offset(code, group.span_close().start())];
+                let line = code.lines().nth(start.line - 1).unwrap_or("");
+                let mut literal_lines = HashSet::new();
+                Self::literal_lines(group.stream(), start.line, &mut literal_lines);
+                macros.entry(key).or_insert_with(|| MacroSource {
+                    text: text.to_string(),
+                    indent: line[..line.len() - line.trim_start().len()].to_string(),
+                    literal_lines,
+                });
+            }
+            Self::collect_macros(code, group.stream(), macros);
+        }
+    }
+
+    // Identifies the macro invocation whose arguments are `tokens[index]` by its name,
+    // delimiter and tokens.
+    fn macro_key(tokens: &[TokenTree], index: usize) -> Option<String> {
+        match (index.checked_sub(2).map(|start| &tokens[start..=index]))? {
+            [TokenTree::Ident(name), TokenTree::Punct(bang), TokenTree::Group(group)]
+                if bang.as_char() == '!' =>
+            {
+                Some(format!(
+                    "{}!{:?}{}",
+                    name,
+                    group.delimiter(),
+                    group.stream()
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    // Collects the lines after `first_line` that start inside a literal of `tokens`.
+    fn literal_lines(tokens: TokenStream, first_line: usize, lines: &mut HashSet<usize>) {
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) => Self::literal_lines(group.stream(), first_line, lines),
+                TokenTree::Literal(literal) => {
+                    let span = literal.span();
+                    lines.extend(span.start().line + 1 - first_line..=span.end().line - first_line);
                 }
                 _ => {}
             }
         }
-        Ok(gen)
+    }
+
+    // Replaces the arguments of the macro invocations in the printed `code` with their
+    // v1 source text, re-indented to where the printer placed the invocation.
+    fn restore_macros(code: &str, macros: &HashMap<String, MacroSource>) -> Result<String> {
+        let tokens: TokenStream = code
+            .parse()
+            .map_err(|e| anyhow!("Error tokenizing generated code: {}", e))?;
+        let mut replacements = Vec::new();
+        Self::macro_replacements(code, tokens, macros, &mut replacements);
+        let mut code = code.to_string();
+        for (range, text) in replacements.into_iter().rev() {
+            code.replace_range(range, &text);
+        }
+        Ok(code)
+    }
+
+    fn macro_replacements(
+        code: &str,
+        tokens: TokenStream,
+        macros: &HashMap<String, MacroSource>,
+        replacements: &mut Vec<(Range<usize>, String)>,
+    ) {
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            let TokenTree::Group(group) = token else {
+                continue;
+            };
+            let source = Self::macro_key(&tokens, index).and_then(|key| macros.get(&key));
+            match source {
+                Some(source) => replacements.push(Self::macro_replacement(code, group, source)),
+                None => Self::macro_replacements(code, group.stream(), macros, replacements),
+            }
+        }
+    }
+
+    fn macro_replacement(
+        code: &str,
+        group: &Group,
+        source: &MacroSource,
+    ) -> (Range<usize>, String) {
+        let start = group.span_open().end();
+        let line = code.lines().nth(start.line - 1).unwrap_or("");
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let lines: Vec<&str> = source.text.split('\n').collect();
+        let text = lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                if index == 0 || source.literal_lines.contains(&index) {
+                    return line.to_string();
+                }
+                if line.trim().is_empty() {
+                    // Only the line of the closing delimiter keeps its indentation
+                    return if index + 1 == lines.len() {
+                        indent.to_string()
+                    } else {
+                        String::new()
+                    };
+                }
+                let line = line
+                    .strip_prefix(source.indent.as_str())
+                    .unwrap_or(line.trim_start());
+                let nested = line.len() - line.trim_start_matches('\t').len();
+                format!("{}{}{}", indent, "    ".repeat(nested), &line[nested..])
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let range =
+            Lexer::byte_offset(code, start)..Lexer::byte_offset(code, group.span_close().start());
+        (range, text)
     }
 
     pub fn parse_vec_to_ast(lines: Vec<String>) -> Result<Vec<Item>> {
@@ -81,20 +472,15 @@ impl Writer {
         }
         Ok(ast_nodes)
     }
-
-    fn validate(code: &str) -> bool {
-        let token_stream: proc_macro2::TokenStream = code.parse().unwrap();
-        syn::parse::<ItemMod>(token_stream.into()).is_ok()
-    }
 }
 
-
-
 #[cfg(test)]
 mod tests {
+    use crate::config::{Config, IndentStyle};
     use crate::lexer::Lexer;
     use crate::writer::Writer;
     use std::fs;
+    use syn::{Item, ItemFn};
 
     #[test]
     fn test_writer_should_generate_with_single_case() {
@@ -108,13 +494,245 @@ mod tests {
 	}: _<T::RuntimeOrigin>(origin, account)
 	verify {
 		ensure!(Registrars::<T>::get().len() as u32 == r + 1, "Registrars not added.");
-	}"#;
+	}
+}"#;
 
         let lexer = Lexer::new(input.to_string());
         let parsed_lines = lexer.parse().unwrap();
-        let gen = Writer::generate_module(parsed_lines).unwrap();
-        assert!(gen.len() > 0)
+        let gen = Writer::generate_module(parsed_lines, &Config::default()).unwrap();
+        assert!(!gen.code.is_empty());
+        assert!(gen.skipped.is_empty());
+        assert!(gen.code.contains("#[extrinsic_call]"));
+        assert!(gen.code.contains("Ok(())"));
+    }
+
+    #[test]
+    fn test_writer_keeps_macro_source_text() {
+        let input = r#"benchmarks! {
+	remark {
+		let caller = whitelisted_caller();
+		ensure!(
+			!RemarkOf::<T>::contains_key(&caller),
+			"already
+	remarked"
+		);
+	}: _(RawOrigin::Signed(caller))
+	impl_benchmark_test_suite!(Pallet, crate::tests::new_test_ext(), crate::tests::Test);
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let config = Config {
+            indent: IndentStyle::Tabs,
+            ..Default::default()
+        };
+        let gen = Writer::generate_module(lines, &config).unwrap();
+        assert!(gen.code.contains(
+            "\t\tensure!(\n\t\t\t!RemarkOf::<T>::contains_key(&caller),\n\t\t\t\"already\n\tremarked\"\n\t\t);\n"
+        ));
+        assert!(gen.code.contains(
+            "\timpl_benchmark_test_suite!(Pallet, crate::tests::new_test_ext(), crate::tests::Test);\n"
+        ));
+    }
+
+    #[test]
+    fn test_writer_accepts_multi_line_calls() {
+        let input = r#"benchmarks! {
+	set_identity {
+		let caller: T::AccountId = whitelisted_caller();
+	}: _(
+		RawOrigin::Signed(caller.clone()),
+		Box::new(create_identity_info::<T>(1)),
+	)
+	verify {
+		assert_last_event::<T>(Event::<T>::IdentitySet { who: caller }.into())
+	}
+	set_subs {
+		let caller: T::AccountId = whitelisted_caller();
+	}: set_subs(RawOrigin::Signed(caller), subs)
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert!(gen.skipped.is_empty(), "{:?}", gen.skipped);
+        let code = gen.code.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(code.contains(
+            "#[extrinsic_call] _(RawOrigin::Signed(caller.clone()), Box::new(create_identity_info::<T>(1)));"
+        ));
+        assert!(code.contains("#[extrinsic_call] set_subs(RawOrigin::Signed(caller), subs);"));
+    }
+
+    #[test]
+    fn test_writer_resolves_common_components() {
+        let input = r#"benchmarks! {
+	_ {
+		let l in 1 .. MAX_LENGTH => ();
+		let u in 0 .. 1000 => fund::<T>(u)?;
+	}
+	remark {
+		let l in _ .. _ => ();
+		let caller = account::<T>(u);
+	}: _(RawOrigin::Signed(caller), vec![0; l as usize])
+	set_code {
+		let c in 0 .. 10;
+	}: _(RawOrigin::Root, vec![0; c as usize])
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert!(gen.skipped.is_empty(), "{:?}", gen.skipped);
+        let code = gen.code.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(code.contains("fn remark( l: Linear<1, MAX_LENGTH>, u: Linear<0, 1000>, )"));
+        assert!(code.contains("{ fund::<T>(u)?; let caller = account::<T>(u);"));
+        assert!(code.contains("fn set_code(c: Linear<0, 10>)"));
+        assert!(!code.contains("fn _"));
+    }
 
+    #[test]
+    fn test_writer_skips_benchmark_with_invalid_component() {
+        let input = r#"benchmarks! {
+	broken {
+		let x in 10;
+	}: _(RawOrigin::Root)
+	remark {
+		let l in 0 .. 100;
+	}: _(RawOrigin::Root, vec![0; l as usize])
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert_eq!(gen.skipped.len(), 1);
+        assert_eq!(gen.skipped[0].name, "broken");
+        assert!(gen.skipped[0].reason.starts_with("Invalid component"));
+        assert!(gen.code.contains("fn remark(l: Linear<0, 100>)"));
+        assert!(!gen.code.contains("fn broken"));
+    }
+
+    #[test]
+    fn test_writer_carries_where_clause() {
+        let input = r#"benchmarks_instance_pallet! {
+	where_clause {
+		where
+			T: pallet_balances::Config,
+	}
+	transfer {
+		let caller = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller))
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert!(gen.code.starts_with(
+            "#[instance_benchmarks(where T: pallet_balances::Config)]\nmod benchmarks {"
+        ));
+        assert!(!gen.code.contains("where_clause"));
+        assert_eq!(gen.benchmarks.len(), 1);
+    }
+
+    #[test]
+    fn test_writer_converts_instance_pallet() {
+        let input = r#"benchmarks_instance_pallet! {
+	on_initialize {
+		let b in 1 .. 10;
+		let caller = whitelisted_caller();
+		<T as Config>::Currency::make_free_balance_be(&caller, 100u32.into());
+		Pallet::<T>::on_initialize(b.into());
+	}: _(RawOrigin::Signed(caller))
+	remark {
+		let caller = whitelisted_caller();
+	}: _(RawOrigin::Signed(caller))
+	verify {
+		ensure!(Pallet::<T>::remarks() == 1, "not stored");
+	}
+	impl_benchmark_test_suite!(Pallet, crate::tests::new_test_ext(), crate::tests::Test);
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert!(gen
+            .code
+            .starts_with("#[instance_benchmarks]\nmod benchmarks {"));
+        assert!(gen.skipped.is_empty(), "{:?}", gen.skipped);
+        // Macro arguments are copied from the v1 source
+        let code = gen.code.replace(char::is_whitespace, "");
+        assert!(code.contains("<TasConfig<I>>::Currency::make_free_balance_be"));
+        assert!(code.contains("Pallet::<T,I>::on_initialize(b.into());"));
+        assert!(code.contains("ensure!(Pallet::<T,I>::remarks()==1"));
+        assert!(!code.contains("Pallet::<T>"));
+        assert!(code.contains("impl_benchmark_test_suite!(Pallet,"));
+    }
+
+    #[test]
+    fn test_writer_converts_block_calls() {
+        let input = r#"benchmarks! {
+	on_initialize {
+		let n in 1 .. 10;
+	}: {
+		let weight = Pallet::<T>::on_initialize(n.into());
+	}
+	verify {
+		assert!(weight.ref_time() > 0);
+	}
+	on_idle {
+	}: { Pallet::<T>::on_idle(0u32.into(), Weight::MAX) }
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert!(gen.skipped.is_empty(), "{:?}", gen.skipped);
+        let code = gen.code.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(code.contains(
+            "let weight; #[block] { weight = Pallet::<T>::on_initialize(n.into()); } assert!(weight.ref_time() > 0);"
+        ));
+        assert!(
+            code.contains("#[block] { Pallet::<T>::on_idle(0u32.into(), Weight::MAX); } Ok(())")
+        );
+        assert!(!code.contains("#[extrinsic_call]"));
+    }
+
+    #[test]
+    fn test_writer_carries_benchmark_attributes() {
+        let input = r#"benchmarks! {
+	#[extra]
+	#[skip_meta]
+	remark {
+		let l in 0 .. 100;
+	}: _(RawOrigin::Root, vec![0; l as usize])
+	#[pov_mode = MaxEncodedLen {
+		Pallet::Storage: Measured
+	}]
+	store {
+	}: _(RawOrigin::Root)
+	plain {
+	}: _(RawOrigin::Root)
+}"#;
+        let lines = Lexer::new(input.to_string()).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert!(gen.skipped.is_empty(), "{:?}", gen.skipped);
+        assert!(gen
+            .code
+            .contains("#[benchmark(extra, skip_meta)]\n    fn remark(l: Linear<0, 100>)"));
+        // Attribute arguments are printed with token spacing
+        let code = gen.code.replace(char::is_whitespace, "");
+        assert!(code
+            .contains("#[benchmark(pov_mode=MaxEncodedLen{Pallet::Storage:Measured})]fnstore()"));
+        assert!(code.contains("#[benchmark]fnplain()"));
+    }
+
+    #[test]
+    fn test_writer_should_follow_config() {
+        let input = r#"benchmarks! {
+	add_registrar {
+		let account = T::Lookup::unlookup(account("registrar", 1, SEED));
+	}: _<T::RuntimeOrigin>(origin, account)
+}"#;
+        let config = Config {
+            module_name: "bench".to_string(),
+            indent: IndentStyle::Tabs,
+            frame_version: "0.9.40".parse().unwrap(),
+            ..Default::default()
+        };
+        let lines = Lexer::with_config(input.to_string(), config.clone())
+            .parse()
+            .unwrap();
+        let gen = Writer::generate_module(lines, &config).unwrap();
+        assert!(gen
+            .code
+            .starts_with("#[benchmarks]\nmod bench {\n\tuse super::*;\n"));
+        assert!(gen.code.contains("\tfn add_registrar() {\n"));
+        assert!(!gen.code.contains("Ok(())"));
     }
 
     #[test]
@@ -125,12 +743,58 @@ mod tests {
         if let Ok(input) = fs::read_to_string(file_path) {
             let lexer = Lexer::new(input.to_string());
             let parsed_lines = lexer.parse().unwrap();
-            let gen = Writer::generate_module(parsed_lines).unwrap();
-            assert!(gen.len() > 0)
+            let gen = Writer::generate_module(parsed_lines, &Config::default()).unwrap();
+            assert!(!gen.code.is_empty());
+            assert!(gen.skipped.is_empty(), "{:?}", gen.skipped);
+            assert_eq!(
+                gen.code.matches("#[extrinsic_call]").count(),
+                gen.benchmarks.len()
+            );
         } else {
             // Handle the case where reading the file fails
             panic!("Failed to read the file at path: {}", file_path);
         }
+    }
 
+    #[test]
+    fn test_writer_resolves_overlapping_names_by_position() {
+        let input = fs::read_to_string("src/fixtures/overlapping_names_v1.rs").unwrap();
+        let lines = Lexer::new(input).parse().unwrap();
+        let gen = Writer::generate_module(lines, &Config::default()).unwrap();
+        assert!(gen.skipped.is_empty(), "{:?}", gen.skipped);
+
+        let file = syn::parse_file(&gen.code).unwrap();
+        let Item::Mod(module) = &file.items[0] else {
+            panic!("expected a module");
+        };
+        let functions: Vec<&ItemFn> = module
+            .content
+            .as_ref()
+            .unwrap()
+            .1
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fn(function) => Some(function),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<String> = functions.iter().map(|f| f.sig.ident.to_string()).collect();
+        assert_eq!(
+            names,
+            [
+                "set_subs_new",
+                "set_subs_old",
+                "set_subs",
+                "rename_sub",
+                "add_sub"
+            ]
+        );
+        for function in functions {
+            let name = function.sig.ident.to_string();
+            let body = quote::quote!(#function).to_string();
+            // Every body carries its own setup and verify, and none of another benchmark
+            assert!(body.contains(&format!("\"{} verify\"", name)), "{}", body);
+            assert_eq!(body.matches(" verify\"").count(), 1, "{}", body);
+        }
     }
 }